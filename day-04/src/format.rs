@@ -0,0 +1,185 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{parse_passports, Passport, KEYS};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Batch,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "batch" => Ok(Self::Batch),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("Unknown format {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    /// A value batch format can't hold, with its key
+    Value(&'static str, String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "Invalid JSON: {}", e),
+            Self::Csv(e) => write!(f, "Invalid CSV: {}", e),
+            Self::Value(key, value) => write!(f, "Invalid value {:?} for {}", value, key),
+        }
+    }
+}
+
+impl From<serde_json::Error> for FormatError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<csv::Error> for FormatError {
+    fn from(e: csv::Error) -> Self {
+        Self::Csv(e)
+    }
+}
+
+impl Passport {
+    fn to_batch(&self) -> String {
+        KEYS.iter()
+            .zip(self.values().iter())
+            .filter_map(|(key, value)| value.as_ref().map(|v| format!("{}:{}", key, v)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Treats empty values as missing, like batch parsing does, and rejects
+    /// values with whitespace since batch format would split them
+    fn normalize(mut self) -> Result<Self, FormatError> {
+        for (key, field) in [
+            ("hgt", &mut self.height),
+            ("hcl", &mut self.hair_color),
+            ("ecl", &mut self.eye_color),
+            ("pid", &mut self.passport_id),
+            ("cid", &mut self.country_id),
+        ] {
+            match field.as_deref() {
+                Some("") => *field = None,
+                Some(value) if value.contains(char::is_whitespace) => {
+                    return Err(FormatError::Value(key, value.to_string()))
+                }
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+}
+
+pub fn serialize(passports: &[Passport], format: Format) -> Result<String, FormatError> {
+    match format {
+        Format::Batch => Ok(passports
+            .iter()
+            .map(|p| p.to_batch() + "\n")
+            .collect::<Vec<String>>()
+            .join("\n")),
+        Format::Json => Ok(serde_json::to_string_pretty(passports)? + "\n"),
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            writer.write_record(KEYS)?;
+            for passport in passports {
+                writer
+                    .write_record(passport.values().iter().map(|v| v.as_deref().unwrap_or("")))?;
+            }
+            let bytes = writer
+                .into_inner()
+                .map_err(|e| csv::Error::from(e.into_error()))?;
+            Ok(String::from_utf8(bytes).unwrap())
+        }
+    }
+}
+
+pub fn deserialize(s: &str, format: Format) -> Result<Vec<Passport>, FormatError> {
+    let passports: Vec<Passport> = match format {
+        Format::Batch => return Ok(parse_passports(s)),
+        Format::Json => serde_json::from_str(s)?,
+        Format::Csv => csv::Reader::from_reader(s.as_bytes())
+            .deserialize()
+            .collect::<Result<Vec<Passport>, csv::Error>>()?,
+    };
+    passports
+        .into_iter()
+        .map(Passport::normalize)
+        .filter(|passport| {
+            passport
+                .as_ref()
+                .map_or(true, |passport| *passport != Passport::default())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE: &str = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd
+byr:1937 iyr:2017 cid:147 hgt:183cm
+
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929
+
+byr:abc hgt: zzz:1
+
+hcl:#cfa07d eyr:2025 pid:166559648
+iyr:2011 ecl:brn hgt:59in";
+
+    #[test]
+    fn test_batch_canonical() {
+        let passports = parse_passports(SAMPLE);
+        let batch = serialize(&passports, Format::Batch).unwrap();
+        assert_eq!(
+            batch,
+            "byr:1937 iyr:2017 eyr:2020 hgt:183cm hcl:#fffffd ecl:gry pid:860033327 cid:147
+
+byr:1929 iyr:2013 eyr:2023 hcl:#cfa07d ecl:amb pid:028048884 cid:350
+
+iyr:2011 eyr:2025 hgt:59in hcl:#cfa07d ecl:brn pid:166559648
+"
+        );
+        assert_eq!(parse_passports(&batch), passports);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let passports = parse_passports(SAMPLE);
+        for format in [Format::Batch, Format::Json, Format::Csv].iter() {
+            let serialized = serialize(&passports, *format).unwrap();
+            let parsed = deserialize(&serialized, *format).unwrap();
+            assert_eq!(parsed, passports);
+            assert_eq!(serialize(&parsed, *format).unwrap(), serialized);
+        }
+    }
+
+    #[test]
+    fn test_import_to_batch() {
+        let json = r#"[{"byr": 1937, "hgt": "", "pid": "860033327"}, {"hcl": ""}]"#;
+        let passports = deserialize(json, Format::Json).unwrap();
+        let batch = serialize(&passports, Format::Batch).unwrap();
+        assert_eq!(batch, "byr:1937 pid:860033327\n");
+        let exported = serialize(&parse_passports(&batch), Format::Json).unwrap();
+        assert_eq!(deserialize(&exported, Format::Json).unwrap(), passports);
+
+        assert!(matches!(
+            deserialize(r#"[{"hgt": "180 cm"}]"#, Format::Json),
+            Err(FormatError::Value("hgt", _))
+        ));
+    }
+}
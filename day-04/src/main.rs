@@ -1,117 +1,73 @@
+use std::env;
 use std::fs;
 use std::num::ParseIntError;
 use std::str::FromStr;
 
-use lazy_static::lazy_static;
-use regex::Regex;
+use records::records;
+use serde::{Deserialize, Serialize};
 
-fn split_once(s: &str) -> (&str, &str) {
-    let mut splitter = s.splitn(2, ':');
-    (splitter.next().unwrap(), splitter.next().unwrap())
-}
+mod format;
+mod merge;
+mod validation;
+
+use format::Format;
+
+/// Keys in the order they're written out in every format
+const KEYS: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 struct Passport {
+    #[serde(rename = "byr", skip_serializing_if = "Option::is_none")]
     birth_year: Option<i32>,
+    #[serde(rename = "iyr", skip_serializing_if = "Option::is_none")]
     issue_year: Option<i32>,
+    #[serde(rename = "eyr", skip_serializing_if = "Option::is_none")]
     exp_year: Option<i32>,
+    #[serde(rename = "hgt", skip_serializing_if = "Option::is_none")]
     height: Option<String>,
+    #[serde(rename = "hcl", skip_serializing_if = "Option::is_none")]
     hair_color: Option<String>,
+    #[serde(rename = "ecl", skip_serializing_if = "Option::is_none")]
     eye_color: Option<String>,
+    #[serde(rename = "pid", skip_serializing_if = "Option::is_none")]
     passport_id: Option<String>,
+    #[serde(rename = "cid", skip_serializing_if = "Option::is_none")]
     country_id: Option<String>,
 }
 
 impl Passport {
-    fn valid_1(&self) -> bool {
-        let required_ints = [self.birth_year, self.issue_year, self.exp_year];
-        let required_strs = [
-            self.height.as_ref(),
-            self.hair_color.as_ref(),
-            self.eye_color.as_ref(),
-            self.passport_id.as_ref(),
-        ];
-        let has_required_ints =
-            required_ints.iter().filter_map(|v| *v).count() == required_ints.len();
-        let has_required_strs =
-            required_strs.iter().filter_map(|v| *v).count() == required_strs.len();
-
-        has_required_ints && has_required_strs
+    /// Field values in the same order as `KEYS`
+    fn values(&self) -> [Option<String>; 8] {
+        [
+            self.birth_year.map(|v| v.to_string()),
+            self.issue_year.map(|v| v.to_string()),
+            self.exp_year.map(|v| v.to_string()),
+            self.height.clone(),
+            self.hair_color.clone(),
+            self.eye_color.clone(),
+            self.passport_id.clone(),
+            self.country_id.clone(),
+        ]
     }
 
-    fn valid(&self) -> bool {
-        let required_ints = [self.birth_year, self.issue_year, self.exp_year];
-        let required_strs = [
-            self.height.as_ref(),
-            self.hair_color.as_ref(),
-            self.eye_color.as_ref(),
-            self.passport_id.as_ref(),
-        ];
-        let has_required_ints =
-            required_ints.iter().filter_map(|v| *v).count() == required_ints.len();
-        let has_required_strs =
-            required_strs.iter().filter_map(|v| *v).count() == required_strs.len();
-
-        if !(has_required_ints && has_required_strs) {
-            return false;
-        }
-
-        let birth_year_valid = self.birth_year.unwrap() >= 1920 && self.birth_year.unwrap() <= 2002;
-        let issue_year_valid = self.issue_year.unwrap() >= 2010 && self.issue_year.unwrap() <= 2020;
-        let exp_year_valid = self.exp_year.unwrap() >= 2020 && self.exp_year.unwrap() <= 2030;
-
-        let detail_valid = self.validate_detail(
-            self.height.as_ref().unwrap(),
-            self.hair_color.as_ref().unwrap(),
-            self.eye_color.as_ref().unwrap(),
-            self.passport_id.as_ref().unwrap(),
-        );
-
-        has_required_ints
-            && has_required_strs
-            && birth_year_valid
-            && issue_year_valid
-            && exp_year_valid
-            && detail_valid
+    fn get(&self, key: &str) -> Option<String> {
+        let idx = KEYS.iter().position(|k| *k == key)?;
+        self.values()[idx].clone()
     }
 
-    fn validate_detail(
-        &self,
-        height: &str,
-        hair_color: &str,
-        eye_color: &str,
-        passport_id: &str,
-    ) -> bool {
-        lazy_static! {
-            static ref HEIGHT_INT_RE: Regex = Regex::new(r"\d+").unwrap();
-            static ref HEIGHT_RE: Regex = Regex::new(r"\d+(cm|in)").unwrap();
-            static ref HAIR_RE: Regex = Regex::new(r"^#[0-9a-f]{6}").unwrap();
-            static ref EYE_RE: Regex = Regex::new(r"^(amb|blu|brn|gry|grn|hzl|oth)$").unwrap();
-            static ref PASSPORT_ID_RE: Regex = Regex::new(r"^\d{9}$").unwrap();
-        }
-        let height_int = HEIGHT_INT_RE
-            .captures(height)
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .as_str()
-            .parse::<i32>()
-            .unwrap();
-
-        let height_valid = HEIGHT_RE.is_match(height)
-            && if height.contains("cm") {
-                height_int >= 150 && height_int <= 193
-            } else {
-                height_int >= 59 && height_int <= 76
-            };
-
-        let hair_color_valid = HAIR_RE.is_match(hair_color);
-
-        let eye_color_valid = EYE_RE.is_match(eye_color);
-
-        let passport_id_valid = PASSPORT_ID_RE.is_match(passport_id);
-
-        height_valid && hair_color_valid && eye_color_valid && passport_id_valid
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "byr" => self.birth_year = value.parse::<i32>().ok(),
+            "iyr" => self.issue_year = value.parse::<i32>().ok(),
+            "eyr" => self.exp_year = value.parse::<i32>().ok(),
+            "hgt" => self.height = Some(value.to_string()),
+            "hcl" => self.hair_color = Some(value.to_string()),
+            "ecl" => self.eye_color = Some(value.to_string()),
+            "pid" => self.passport_id = Some(value.to_string()),
+            "cid" => self.country_id = Some(value.to_string()),
+            _ => {}
+        };
     }
 }
 
@@ -121,42 +77,149 @@ impl FromStr for Passport {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut passport = Passport::default();
 
-        for item in s.split(&[' ', '\n'][..]) {
-            match split_once(item) {
-                ("byr", byr) => passport.birth_year = byr.parse::<i32>().ok(),
-                ("iyr", iyr) => passport.issue_year = iyr.parse::<i32>().ok(),
-                ("eyr", eyr) => passport.exp_year = eyr.parse::<i32>().ok(),
-                ("hgt", hgt) => passport.height = Some(hgt.to_string()),
-                ("hcl", hcl) => passport.hair_color = Some(hcl.to_string()),
-                ("ecl", ecl) => passport.eye_color = Some(ecl.to_string()),
-                ("pid", pid) => passport.passport_id = Some(pid.to_string()),
-                ("cid", cid) => passport.country_id = Some(cid.to_string()),
-                _ => {}
-            };
+        // Empty values are treated the same as missing ones so that every
+        // format round-trips them identically
+        for (key, value) in s
+            .split_whitespace()
+            .filter_map(|item| item.split_once(':'))
+            .filter(|(_, value)| !value.is_empty())
+        {
+            passport.set(key, value);
         }
 
         Ok(passport)
     }
 }
 
+/// Records without any recognised fields are dropped, since they can't be
+/// told apart from noise and would otherwise be lost on a round trip
 fn parse_passports(s: &str) -> Vec<Passport> {
-    s.split("\n\n")
-        .map(Passport::from_str)
+    records(s)
+        .map(|record| Passport::from_str(&record.join("\n")))
         .filter_map(Result::ok)
+        .filter(|passport| *passport != Passport::default())
         .collect()
 }
 
+fn read_input(path: Option<&String>) -> String {
+    fs::read_to_string(path.map_or("./day-04/input.txt", String::as_str)).unwrap()
+}
+
 fn main() {
-    let input = fs::read_to_string("./day-04/input.txt").unwrap();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // Each `--profile NAME=RULES` defines another profile, see
+    // `Profiles::define`
+    let mut profiles = validation::Profiles::default();
+    while let Some(idx) = args.iter().position(|arg| arg == "--profile") {
+        args.remove(idx);
+        if idx == args.len() {
+            eprintln!("Missing a value for --profile");
+            return;
+        }
+        let definition = args.remove(idx);
+        if let Err(e) = profiles.define(&definition) {
+            eprintln!("{}", e);
+            return;
+        }
+    }
 
-    let passports = parse_passports(&input);
+    match args.as_slice() {
+        // Writes batch input out in another format
+        [command, format, rest @ ..] if command == "export" => {
+            let passports = parse_passports(&read_input(rest.first()));
+            let output = Format::from_str(format)
+                .map_err(|e| e.to_string())
+                .and_then(|format| {
+                    format::serialize(&passports, format).map_err(|e| e.to_string())
+                });
+            match output {
+                Ok(output) => print!("{}", output),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        // Reads another format back in as canonical batch format
+        [command, format, rest @ ..] if command == "import" => {
+            let output = Format::from_str(format)
+                .map_err(|e| e.to_string())
+                .and_then(|format| {
+                    let passports = format::deserialize(&read_input(rest.first()), format)
+                        .map_err(|e| e.to_string())?;
+                    format::serialize(&passports, Format::Batch).map_err(|e| e.to_string())
+                });
+            match output {
+                Ok(output) => print!("{}", output),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        // Merges records sharing a key, printing the merged batch and
+        // reporting conflicts separately
+        [command, rest @ ..] if command == "merge" => {
+            let key = rest.first().map_or("pid", String::as_str);
+            let strict = profiles.profile("strict").unwrap();
+            let report =
+                merge::merge_passports(&parse_passports(&read_input(rest.get(1))), key, |p| {
+                    strict.valid(p)
+                });
+            let merged: Vec<Passport> = report.merged.iter().map(|m| m.passport.clone()).collect();
+            print!("{}", format::serialize(&merged, Format::Batch).unwrap());
+            for conflict in report.merged.iter().flat_map(|m| m.conflicts.iter()) {
+                eprintln!(
+                    "Conflict on {}={}: {} has values {}",
+                    key,
+                    conflict.group,
+                    conflict.key,
+                    conflict.values.join(", ")
+                );
+            }
+            eprintln!("Newly valid: {}", report.newly_valid);
+            return;
+        }
+        [command] if command == "profiles" => {
+            for profile in profiles.iter() {
+                println!("{}", profile.name);
+                for rule in profile.rules.iter() {
+                    println!("  {}", rule.describe());
+                }
+            }
+            return;
+        }
+        // Counts passports valid under a profile, or several joined with `+`
+        [command, spec, rest @ ..] if command == "validate" => {
+            let profile = match profiles.profile(spec) {
+                Some(profile) => profile,
+                None => {
+                    let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+                    eprintln!(
+                        "Unknown profile {}, expected one of {}",
+                        spec,
+                        names.join(", ")
+                    );
+                    return;
+                }
+            };
+            let passports = parse_passports(&read_input(rest.first()));
+            println!("{}", passports.iter().filter(|p| profile.valid(p)).count());
+            return;
+        }
+        _ => {}
+    }
+
+    let passports = parse_passports(&read_input(None));
+    let presence = profiles.profile("presence").unwrap();
+    let strict = profiles.profile("strict").unwrap();
 
     println!(
         "Part 1: {}",
-        passports.iter().filter(|p| p.valid_1()).count()
+        passports.iter().filter(|p| presence.valid(p)).count()
     );
 
-    println!("Part 2: {}", passports.iter().filter(|p| p.valid()).count());
+    println!(
+        "Part 2: {}",
+        passports.iter().filter(|p| strict.valid(p)).count()
+    );
 }
 
 #[cfg(test)]
@@ -180,7 +243,8 @@ hcl:#cfa07d eyr:2025 pid:166559648
 iyr:2011 ecl:brn hgt:59in";
         let passports = parse_passports(sample_1);
         assert_eq!(passports.len(), 4);
-        assert_eq!(passports.iter().filter(|p| p.valid_1()).count(), 2);
+        let presence = validation::Profiles::default().profile("presence").unwrap();
+        assert_eq!(passports.iter().filter(|p| presence.valid(p)).count(), 2);
     }
 
     #[test]
@@ -198,9 +262,10 @@ ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277
 hgt:59cm ecl:zzz
 eyr:2038 hcl:74454a iyr:2023
 pid:3556412378 byr:2007";
+        let strict = validation::Profiles::default().profile("strict").unwrap();
         let passports = parse_passports(sample_invalid);
         assert_eq!(passports.len(), 4);
-        assert_eq!(passports.iter().filter(|p| p.valid()).count(), 0);
+        assert_eq!(passports.iter().filter(|p| strict.valid(p)).count(), 0);
 
         let sample_valid = "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980
 hcl:#623a2f
@@ -216,6 +281,24 @@ eyr:2022
 iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719";
         let passports = parse_passports(sample_valid);
         assert_eq!(passports.len(), 4);
-        assert_eq!(passports.iter().filter(|p| p.valid()).count(), 4);
+        assert_eq!(passports.iter().filter(|p| strict.valid(p)).count(), 4);
+    }
+
+    #[test]
+    fn test_messy_separators() {
+        let sample = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd\r
+byr:1937 iyr:2017 cid:147 hgt:183cm\r
+\r
+  \t
+iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
+hcl:#cfa07d byr:1929
+
+foo:bar
+
+";
+        let passports = parse_passports(sample);
+        assert_eq!(passports.len(), 2);
+        assert_eq!(passports[0].height, Some("183cm".to_string()));
+        assert_eq!(passports[1].birth_year, Some(1929));
     }
 }
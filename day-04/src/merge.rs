@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use super::{Passport, KEYS};
+
+/// A field with more than one distinct value across records in a group
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub group: String,
+    pub key: &'static str,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct MergedPassport {
+    pub passport: Passport,
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug)]
+pub struct MergeReport {
+    pub merged: Vec<MergedPassport>,
+    /// Merged passports that are valid even though none of their records were
+    pub newly_valid: usize,
+}
+
+fn merge_group(group: &[&Passport], group_value: Option<&str>) -> MergedPassport {
+    let mut passport = Passport::default();
+    let mut conflicts = vec![];
+
+    for (idx, key) in KEYS.iter().enumerate() {
+        let mut values: Vec<String> = vec![];
+        for value in group.iter().filter_map(|p| p.values()[idx].clone()) {
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        // The first value seen wins, but any others are flagged
+        if let Some(value) = values.first() {
+            passport.set(key, value);
+        }
+        if values.len() > 1 {
+            conflicts.push(Conflict {
+                group: group_value.unwrap_or_default().to_string(),
+                key,
+                values,
+            });
+        }
+    }
+
+    MergedPassport {
+        passport,
+        conflicts,
+    }
+}
+
+/// Groups passports by the value of `key`, merging complementary fields
+/// within each group. Passports without a value for `key` are left alone.
+pub fn merge_passports<F>(passports: &[Passport], key: &str, is_valid: F) -> MergeReport
+where
+    F: Fn(&Passport) -> bool,
+{
+    let mut groups: Vec<(Option<String>, Vec<&Passport>)> = vec![];
+    let mut group_idx: HashMap<String, usize> = HashMap::new();
+
+    for passport in passports {
+        match passport.get(key) {
+            Some(value) => {
+                let idx = *group_idx.entry(value.clone()).or_insert_with(|| {
+                    groups.push((Some(value), vec![]));
+                    groups.len() - 1
+                });
+                groups[idx].1.push(passport);
+            }
+            None => groups.push((None, vec![passport])),
+        }
+    }
+
+    let merged: Vec<MergedPassport> = groups
+        .iter()
+        .map(|(value, group)| merge_group(group, value.as_deref()))
+        .collect();
+
+    let newly_valid = groups
+        .iter()
+        .zip(merged.iter())
+        .filter(|((_, group), m)| is_valid(&m.passport) && !group.iter().any(|p| is_valid(p)))
+        .count();
+
+    MergeReport {
+        merged,
+        newly_valid,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{parse_passports, validation};
+    use super::*;
+
+    #[test]
+    fn test_merge_complementary() {
+        let passports = parse_passports(
+            "pid:087499704 hgt:74in ecl:grn
+
+iyr:2012 eyr:2030 byr:1980 pid:087499704
+
+hcl:#623a2f pid:087499704
+
+pid:896056539 hcl:#a97842 hgt:165cm
+
+eyr:2029 ecl:blu",
+        );
+        let report = merge_passports(&passports, "pid", |p| {
+            validation::Profiles::default()
+                .profile("strict")
+                .unwrap()
+                .valid(p)
+        });
+        assert_eq!(report.merged.len(), 3);
+        assert_eq!(
+            report.merged[0].passport,
+            parse_passports(
+                "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f"
+            )[0]
+        );
+        assert!(report.merged.iter().all(|m| m.conflicts.is_empty()));
+        assert_eq!(report.newly_valid, 1);
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let passports = parse_passports(
+            "pid:087499704 hgt:74in ecl:grn
+
+hgt:180cm pid:087499704 ecl:grn",
+        );
+        let report = merge_passports(&passports, "pid", |p| {
+            validation::Profiles::default()
+                .profile("presence")
+                .unwrap()
+                .valid(p)
+        });
+        assert_eq!(report.merged.len(), 1);
+        assert_eq!(report.merged[0].passport.height, Some("74in".to_string()));
+        assert_eq!(
+            report.merged[0].conflicts,
+            vec![Conflict {
+                group: "087499704".to_string(),
+                key: "hgt",
+                values: vec!["74in".to_string(), "180cm".to_string()],
+            }]
+        );
+        assert_eq!(report.newly_valid, 0);
+    }
+}
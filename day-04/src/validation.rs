@@ -0,0 +1,275 @@
+use std::rc::Rc;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{Passport, KEYS};
+
+pub trait Rule {
+    fn check(&self, passport: &Passport) -> bool;
+
+    fn describe(&self) -> String;
+}
+
+/// Field has any value at all
+pub struct Present(pub &'static str);
+
+impl Rule for Present {
+    fn check(&self, passport: &Passport) -> bool {
+        passport.get(self.0).is_some()
+    }
+
+    fn describe(&self) -> String {
+        format!("{} present", self.0)
+    }
+}
+
+/// Field is a year within an inclusive range
+pub struct YearBetween(pub &'static str, pub i32, pub i32);
+
+impl Rule for YearBetween {
+    fn check(&self, passport: &Passport) -> bool {
+        passport
+            .get(self.0)
+            .and_then(|v| v.parse::<i32>().ok())
+            .is_some_and(|year| (self.1..=self.2).contains(&year))
+    }
+
+    fn describe(&self) -> String {
+        format!("{} between {} and {}", self.0, self.1, self.2)
+    }
+}
+
+/// Height is in cm or in and within the range allowed for that unit
+pub struct HeightInRange;
+
+impl Rule for HeightInRange {
+    fn check(&self, passport: &Passport) -> bool {
+        lazy_static! {
+            static ref HEIGHT_RE: Regex = Regex::new(r"(?P<num>\d+)(?P<unit>cm|in)").unwrap();
+        }
+        let height = match passport.height.as_ref() {
+            Some(height) => height,
+            None => return false,
+        };
+        HEIGHT_RE.captures(height).is_some_and(|cap| {
+            let height_int = cap["num"].parse::<i32>().unwrap_or(0);
+            match &cap["unit"] {
+                "cm" => (150..=193).contains(&height_int),
+                _ => (59..=76).contains(&height_int),
+            }
+        })
+    }
+
+    fn describe(&self) -> String {
+        String::from("hgt between 150cm and 193cm or 59in and 76in")
+    }
+}
+
+/// Field matches a regular expression
+pub struct Matches(pub &'static str, pub Regex);
+
+impl Rule for Matches {
+    fn check(&self, passport: &Passport) -> bool {
+        passport.get(self.0).is_some_and(|v| self.1.is_match(&v))
+    }
+
+    fn describe(&self) -> String {
+        format!("{} matches {}", self.0, self.1)
+    }
+}
+
+#[derive(Clone)]
+pub struct Profile {
+    pub name: String,
+    pub rules: Vec<Rc<dyn Rule>>,
+}
+
+impl Profile {
+    pub fn valid(&self, passport: &Passport) -> bool {
+        self.rules.iter().all(|rule| rule.check(passport))
+    }
+}
+
+fn presence_rules() -> Vec<Rc<dyn Rule>> {
+    KEYS.iter()
+        .filter(|key| **key != "cid")
+        .map(|key| Rc::new(Present(key)) as Rc<dyn Rule>)
+        .collect()
+}
+
+fn field(key: &str) -> Result<&'static str, String> {
+    KEYS.iter()
+        .find(|k| **k == key)
+        .copied()
+        .ok_or_else(|| format!("Unknown field {}", key))
+}
+
+fn year(s: &str) -> Result<i32, String> {
+    s.parse::<i32>().map_err(|_| format!("Invalid year {}", s))
+}
+
+/// Parses a rule written as `present KEY`, `year KEY MIN MAX`,
+/// `matches KEY REGEX` or `height`
+pub fn parse_rule(s: &str) -> Result<Rc<dyn Rule>, String> {
+    let parts: Vec<&str> = s.trim().splitn(3, ' ').collect();
+    match parts.as_slice() {
+        ["present", key] => Ok(Rc::new(Present(field(key)?))),
+        ["year", key, range] => match range.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [min, max] => Ok(Rc::new(YearBetween(field(key)?, year(min)?, year(max)?))),
+            _ => Err(format!(
+                "Expected a minimum and maximum year, found {:?}",
+                range
+            )),
+        },
+        ["matches", key, re] => {
+            let key = field(key)?;
+            Regex::new(re)
+                .map(|re| Rc::new(Matches(key, re)) as Rc<dyn Rule>)
+                .map_err(|e| e.to_string())
+        }
+        ["height"] => Ok(Rc::new(HeightInRange)),
+        _ => Err(format!("Unknown rule {:?}", s)),
+    }
+}
+
+/// Profiles that can be looked up by name, starting with the builtin ones
+#[derive(Clone)]
+pub struct Profiles(Vec<Profile>);
+
+impl Default for Profiles {
+    fn default() -> Self {
+        Profiles(vec![
+            Profile {
+                name: String::from("presence"),
+                rules: presence_rules(),
+            },
+            Profile {
+                name: String::from("strict"),
+                rules: vec![
+                    Rc::new(YearBetween("byr", 1920, 2002)),
+                    Rc::new(YearBetween("iyr", 2010, 2020)),
+                    Rc::new(YearBetween("eyr", 2020, 2030)),
+                    Rc::new(HeightInRange),
+                    Rc::new(Matches("hcl", Regex::new(r"^#[0-9a-f]{6}").unwrap())),
+                    Rc::new(Matches(
+                        "ecl",
+                        Regex::new(r"^(amb|blu|brn|gry|grn|hzl|oth)$").unwrap(),
+                    )),
+                    Rc::new(Matches("pid", Regex::new(r"^\d{9}$").unwrap())),
+                ],
+            },
+        ])
+    }
+}
+
+impl Profiles {
+    pub fn iter(&self) -> impl Iterator<Item = &Profile> {
+        self.0.iter()
+    }
+
+    /// Adds a profile, replacing any with the same name
+    pub fn register(&mut self, profile: Profile) {
+        match self.0.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.0.push(profile),
+        }
+    }
+
+    /// Registers a profile written as `NAME=ITEM;ITEM;...`, where each item
+    /// is either the name of a registered profile to include or a rule for
+    /// `parse_rule`, e.g. `cid-required=presence;present cid`
+    pub fn define(&mut self, definition: &str) -> Result<(), String> {
+        let (name, items) = definition
+            .split_once('=')
+            .ok_or_else(|| format!("Expected NAME=RULES, found {:?}", definition))?;
+        let name = name.trim();
+        // Names joined with `+` combine profiles, so a name can't contain one
+        if name.is_empty() || name.contains('+') {
+            return Err(format!("Invalid profile name {:?}", name));
+        }
+        let mut rules = vec![];
+        for item in items
+            .split(';')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            match self.0.iter().find(|p| p.name == item) {
+                Some(profile) => rules.extend(profile.rules.iter().cloned()),
+                None => rules.push(parse_rule(item)?),
+            }
+        }
+        self.register(Profile {
+            name: name.to_string(),
+            rules,
+        });
+        Ok(())
+    }
+
+    /// Looks up a profile by name, where several names joined with `+`
+    /// combine the rules of each, e.g. `strict+cid-required`
+    pub fn profile(&self, spec: &str) -> Option<Profile> {
+        let mut rules = vec![];
+        for name in spec.split('+') {
+            let profile = self.0.iter().find(|p| p.name == name)?;
+            rules.extend(profile.rules.iter().cloned());
+        }
+        Some(Profile {
+            name: spec.to_string(),
+            rules,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::parse_passports;
+    use super::*;
+
+    #[test]
+    fn test_builtin_rules() {
+        let passports = parse_passports(
+            "hgt:190 hcl:#123abc ecl:amb pid:000000001 byr:1920 iyr:2010 eyr:2030
+
+hgt:190cm hcl:#123abc ecl:amb pid:000000001 byr:1920 iyr:2010 eyr:2030
+
+hgt:190cm hcl:#123abc ecl:amb pid:000000001 byr:1920 iyr:2010 eyr:2030 cid:1",
+        );
+        let profiles = Profiles::default();
+        let strict = profiles.profile("strict").unwrap();
+        assert!(!strict.valid(&passports[0]));
+        assert!(strict.valid(&passports[1]));
+        assert!(profiles.profile("presence").unwrap().valid(&passports[0]));
+        assert!(profiles.profile("cid-required").is_none());
+    }
+
+    #[test]
+    fn test_user_profiles() {
+        let passports = parse_passports(
+            "hgt:190cm hcl:#123abc ecl:amb pid:000000001 byr:1920 iyr:2010 eyr:2030
+
+hgt:190cm hcl:#123abc ecl:amb pid:000000001 byr:1920 iyr:2010 eyr:2030 cid:1",
+        );
+        let mut profiles = Profiles::default();
+        profiles
+            .define("cid-required=presence; present cid")
+            .unwrap();
+        profiles
+            .define("old=year byr 1900 1930;matches ecl ^amb$")
+            .unwrap();
+        let cid_required = profiles.profile("cid-required").unwrap();
+        assert!(!cid_required.valid(&passports[0]));
+        assert!(cid_required.valid(&passports[1]));
+        assert!(profiles.profile("old").unwrap().valid(&passports[0]));
+        assert_eq!(profiles.iter().count(), 4);
+
+        let combined = profiles.profile("strict+cid-required").unwrap();
+        assert_eq!(combined.rules.len(), 15);
+        assert!(profiles.profile("strict+unknown").is_none());
+        assert!(profiles.define("bad=present xyz").is_err());
+        assert!(profiles.define("bad=year byr 1900").is_err());
+        assert!(profiles.define("no rules").is_err());
+        assert!(profiles.define("=present cid").is_err());
+        assert!(profiles.define("a+b=present cid").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::encode_ticket_seat;
+use super::seat_map::{Seat, SeatMap};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preference {
+    Any,
+    Window,
+    Aisle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Party {
+    pub name: String,
+    pub size: usize,
+    pub preference: Preference,
+}
+
+impl FromStr for Party {
+    type Err = String;
+
+    /// Parses `name:size` with an optional `:window` or `:aisle` suffix
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let size = parts
+            .get(1)
+            .and_then(|size| size.parse::<usize>().ok())
+            .ok_or(format!("Invalid party {}", s))?;
+        let preference = match parts.get(2) {
+            None => Preference::Any,
+            Some(&"window") => Preference::Window,
+            Some(&"aisle") => Preference::Aisle,
+            Some(other) => return Err(format!("Unknown preference {}", other)),
+        };
+        Ok(Party {
+            name: parts[0].to_string(),
+            size,
+            preference,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Assignment {
+    pub party: String,
+    pub seats: Vec<Seat>,
+    pub passes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NotEnoughSeats {
+    pub party: String,
+    pub size: usize,
+    pub free: usize,
+}
+
+impl fmt::Display for NotEnoughSeats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Party {} needs {} seats but only {} are free",
+            self.party, self.size, self.free
+        )
+    }
+}
+
+/// Columns either side of a single aisle down the middle of the plane
+fn aisle_cols(cols: i64) -> Vec<i64> {
+    if cols < 2 {
+        vec![]
+    } else if cols % 2 == 0 {
+        vec![cols / 2 - 1, cols / 2]
+    } else {
+        vec![cols / 2]
+    }
+}
+
+fn satisfies(seats: &[Seat], preference: Preference, cols: i64) -> bool {
+    match preference {
+        Preference::Any => true,
+        Preference::Window => seats.iter().any(|(_, col)| *col == 0 || *col == cols - 1),
+        Preference::Aisle => seats.iter().any(|(_, col)| aisle_cols(cols).contains(col)),
+    }
+}
+
+/// Free runs of `size` adjacent seats within a single row, front row first
+fn contiguous_runs(seat_map: &SeatMap, size: usize) -> Vec<Vec<Seat>> {
+    let (rows, cols) = (seat_map.layout.rows, seat_map.layout.cols);
+    (0..rows)
+        .flat_map(|row| (0..=(cols - size as i64)).map(move |col| (row, col)))
+        .map(|(row, col)| (col..(col + size as i64)).map(|c| (row, c)).collect())
+        .filter(|run: &Vec<Seat>| run.iter().all(|(r, c)| !seat_map.is_occupied(*r, *c)))
+        .collect()
+}
+
+/// Seats each party in turn, filling the plane from the front. Parties are
+/// kept together in one row where possible, preferring a run that meets their
+/// window or aisle preference, and are otherwise split across the front-most
+/// free seats. If any party doesn't fit, no seats are taken.
+pub fn allocate_seats(
+    seat_map: &mut SeatMap,
+    parties: &[Party],
+) -> Result<Vec<Assignment>, NotEnoughSeats> {
+    let mut assignments = vec![];
+    let original = seat_map.clone();
+
+    for party in parties {
+        let free = seat_map.empty_seats();
+        if free.len() < party.size {
+            *seat_map = original;
+            return Err(NotEnoughSeats {
+                party: party.name.clone(),
+                size: party.size,
+                free: free.len(),
+            });
+        }
+
+        let runs = if party.size as i64 <= seat_map.layout.cols {
+            contiguous_runs(seat_map, party.size)
+        } else {
+            vec![]
+        };
+        let seats = runs
+            .iter()
+            .find(|run| satisfies(run, party.preference, seat_map.layout.cols))
+            .or_else(|| runs.first())
+            .cloned()
+            .unwrap_or_else(|| free.into_iter().take(party.size).collect());
+
+        for (row, col) in seats.iter() {
+            seat_map.occupy(*row, *col);
+        }
+        assignments.push(Assignment {
+            party: party.name.clone(),
+            passes: seats
+                .iter()
+                .map(|(row, col)| encode_ticket_seat(&seat_map.layout, *row, *col).unwrap())
+                .collect(),
+            seats,
+        });
+    }
+
+    Ok(assignments)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::SeatLayout;
+    use super::*;
+
+    fn small_map(taken: &[&str]) -> SeatMap {
+        let layout = SeatLayout {
+            rows: 4,
+            cols: 4,
+            row_multiplier: 4,
+            ..SeatLayout::default()
+        };
+        SeatMap::from_passes(&layout, taken).unwrap()
+    }
+
+    #[test]
+    fn test_allocate_together() {
+        let mut seat_map = small_map(&["FFLR", "FBLL"]);
+        let parties: Vec<Party> = ["a:2", "b:3:window", "c:2:aisle", "d:4", "e:3"]
+            .iter()
+            .map(|p| p.parse().unwrap())
+            .collect();
+        let assignments = allocate_seats(&mut seat_map, &parties).unwrap();
+
+        assert_eq!(assignments[0].seats, vec![(0, 2), (0, 3)]);
+        assert_eq!(assignments[0].passes, vec!["FFRL", "FFRR"]);
+        assert_eq!(assignments[1].seats, vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(assignments[2].seats, vec![(2, 0), (2, 1)]);
+        assert_eq!(assignments[3].seats, vec![(3, 0), (3, 1), (3, 2), (3, 3)]);
+        // No row has three free seats left, so the party is split up
+        assert_eq!(assignments[4].seats, vec![(0, 0), (2, 2), (2, 3)]);
+        assert!(seat_map.empty_seats().is_empty());
+    }
+
+    #[test]
+    fn test_allocate_not_enough_seats() {
+        let mut seat_map = small_map(&[]);
+        let parties = vec!["a:10".parse().unwrap(), "b:7".parse().unwrap()];
+        assert_eq!(
+            allocate_seats(&mut seat_map, &parties),
+            Err(NotEnoughSeats {
+                party: "b".to_string(),
+                size: 7,
+                free: 6,
+            })
+        );
+        // The first party's seats are given back
+        assert_eq!(seat_map.empty_seats().len(), 16);
+        assert!("a:x".parse::<Party>().is_err());
+    }
+}
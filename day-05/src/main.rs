@@ -1,62 +1,296 @@
+use std::env;
+use std::fmt;
 use std::fs;
 
-fn calculate_seat_idx(num_seats: i64, ticket: &str) -> i64 {
+mod allocate;
+mod seat_map;
+
+use allocate::Party;
+use seat_map::SeatMap;
+
+#[derive(Debug, PartialEq)]
+enum PassError {
+    Length { expected: usize, found: usize },
+    Letter(char),
+    OutOfRange(i64),
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length { expected, found } => {
+                write!(f, "Expected {} letters, found {}", expected, found)
+            }
+            Self::Letter(c) => write!(f, "Unexpected letter {}", c),
+            Self::OutOfRange(idx) => write!(f, "Seat index {} is outside the plane", idx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SeatLayout {
+    rows: i64,
+    cols: i64,
+    /// Seat ids are calculated as `row * row_multiplier + col`
+    row_multiplier: i64,
+    /// Letters selecting the lower and upper half of the remaining rows
+    row_letters: (char, char),
+    /// Letters selecting the lower and upper half of the remaining columns
+    col_letters: (char, char),
+}
+
+impl Default for SeatLayout {
+    fn default() -> Self {
+        SeatLayout {
+            rows: 128,
+            cols: 8,
+            row_multiplier: 8,
+            row_letters: ('F', 'B'),
+            col_letters: ('L', 'R'),
+        }
+    }
+}
+
+/// Number of halvings needed to narrow `num_seats` down to a single seat
+fn split_len(num_seats: i64) -> usize {
+    (64 - (num_seats - 1).max(0).leading_zeros()) as usize
+}
+
+impl SeatLayout {
+    fn row_len(&self) -> usize {
+        split_len(self.rows)
+    }
+
+    fn col_len(&self) -> usize {
+        split_len(self.cols)
+    }
+
+    fn pass_len(&self) -> usize {
+        self.row_len() + self.col_len()
+    }
+
+    fn seat_id(&self, row: i64, col: i64) -> i64 {
+        row * self.row_multiplier + col
+    }
+
+    /// Checks the plane has seats and each pair of letters can tell the two
+    /// halves apart
+    fn validate(&self) -> Result<(), String> {
+        if self.rows <= 0 || self.cols <= 0 || self.row_multiplier <= 0 {
+            return Err(String::from(
+                "Rows, columns and the multiplier must be positive",
+            ));
+        }
+        for (lower, upper) in [self.row_letters, self.col_letters] {
+            if lower == upper {
+                return Err(format!("Letters {}{} must differ", lower, upper));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn calculate_seat_idx(
+    num_seats: i64,
+    letters: (char, char),
+    ticket: &str,
+) -> Result<i64, PassError> {
     let mut seat_idx: i64 = 0;
-    let mut search_len: i64 = num_seats;
 
+    // Each letter halves the remaining seats, so the upper half letter is a
+    // binary 1 and the lower half letter a 0
     for ticket_char in ticket.chars() {
-        search_len = ((search_len as f64) / 2.0).floor() as i64;
-        if ticket_char == 'B' || ticket_char == 'R' {
-            seat_idx += search_len;
+        seat_idx *= 2;
+        if ticket_char == letters.1 {
+            seat_idx += 1;
+        } else if ticket_char != letters.0 {
+            return Err(PassError::Letter(ticket_char));
         }
     }
-    seat_idx
+    if seat_idx >= num_seats {
+        return Err(PassError::OutOfRange(seat_idx));
+    }
+    Ok(seat_idx)
 }
 
-fn calculate_ticket_seat_id(num_rows: i64, num_cols: i64, ticket: &str) -> i64 {
-    (calculate_seat_idx(
-        num_rows,
+fn calculate_ticket_seat(layout: &SeatLayout, ticket: &str) -> Result<(i64, i64), PassError> {
+    let found = ticket.chars().count();
+    if found != layout.pass_len() {
+        return Err(PassError::Length {
+            expected: layout.pass_len(),
+            found,
+        });
+    }
+    let (row_part, col_part) = ticket.split_at(
         ticket
-            .chars()
-            .filter(|c| *c == 'F' || *c == 'B')
-            .collect::<String>()
-            .as_str(),
-    ) * 8)
-        + calculate_seat_idx(
-            num_cols,
-            ticket
-                .chars()
-                .filter(|c| *c == 'L' || *c == 'R')
-                .collect::<String>()
-                .as_str(),
-        )
-}
-
-fn find_missing_seat_id(num_rows: i64, num_cols: i64, tickets: Vec<&str>) -> Option<i64> {
-    let seat_ids: Vec<i64> = tickets
-        .iter()
-        .map(|ticket| calculate_ticket_seat_id(num_rows, num_cols, ticket))
-        .collect();
-    for row_idx in 1..(num_rows - 1) {
-        for col_idx in 0..num_cols {
-            let seat_id = (row_idx * 8) + col_idx;
-            if !seat_ids.contains(&seat_id)
-                && seat_ids.contains(&(seat_id - 1))
-                && seat_ids.contains(&(seat_id + 1))
-            {
-                return Some(seat_id);
+            .char_indices()
+            .nth(layout.row_len())
+            .map_or(ticket.len(), |(idx, _)| idx),
+    );
+    Ok((
+        calculate_seat_idx(layout.rows, layout.row_letters, row_part)?,
+        calculate_seat_idx(layout.cols, layout.col_letters, col_part)?,
+    ))
+}
+
+fn calculate_ticket_seat_id(layout: &SeatLayout, ticket: &str) -> Result<i64, PassError> {
+    let (row, col) = calculate_ticket_seat(layout, ticket)?;
+    Ok(layout.seat_id(row, col))
+}
+
+fn encode_seat_idx(num_seats: i64, letters: (char, char), seat_idx: i64) -> String {
+    (0..split_len(num_seats))
+        .rev()
+        .map(|bit| {
+            if seat_idx & (1 << bit) == 0 {
+                letters.0
+            } else {
+                letters.1
             }
+        })
+        .collect()
+}
+
+fn encode_ticket_seat(layout: &SeatLayout, row: i64, col: i64) -> Result<String, PassError> {
+    if !(0..layout.rows).contains(&row) {
+        return Err(PassError::OutOfRange(row));
+    }
+    if !(0..layout.cols).contains(&col) {
+        return Err(PassError::OutOfRange(col));
+    }
+    Ok(encode_seat_idx(layout.rows, layout.row_letters, row)
+        + &encode_seat_idx(layout.cols, layout.col_letters, col))
+}
+
+/// Inverse of `SeatLayout::seat_id`, which is only unambiguous when the row
+/// multiplier is at least the number of columns
+fn encode_ticket_seat_id(layout: &SeatLayout, seat_id: i64) -> Result<String, PassError> {
+    if seat_id < 0 || layout.row_multiplier < layout.cols {
+        return Err(PassError::OutOfRange(seat_id));
+    }
+    encode_ticket_seat(
+        layout,
+        seat_id / layout.row_multiplier,
+        seat_id % layout.row_multiplier,
+    )
+    .map_err(|_| PassError::OutOfRange(seat_id))
+}
+
+fn find_missing_seat_id(layout: &SeatLayout, tickets: Vec<&str>) -> Option<i64> {
+    SeatMap::from_passes(layout, &tickets)
+        .unwrap()
+        .missing_seat_ids()
+        .first()
+        .copied()
+}
+
+/// Reads `--rows`, `--cols`, `--multiplier` and `--letters` flags over the
+/// default layout, returning any remaining positional arguments
+fn parse_layout(args: &[String]) -> Result<(SeatLayout, Vec<String>), String> {
+    let mut layout = SeatLayout::default();
+    let mut multiplier = None;
+    let mut positional = vec![];
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let flag = arg.as_str();
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing a value for {}", flag))
+        };
+        let number = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid number {} for {}", value, flag))
+        };
+        match flag {
+            "--rows" => layout.rows = number(value()?)?,
+            "--cols" => layout.cols = number(value()?)?,
+            "--multiplier" => multiplier = Some(number(value()?)?),
+            "--letters" => match value()?.chars().collect::<Vec<char>>().as_slice() {
+                [lower_row, upper_row, lower_col, upper_col] => {
+                    layout.row_letters = (*lower_row, *upper_row);
+                    layout.col_letters = (*lower_col, *upper_col);
+                }
+                _ => return Err(String::from("Expected four letters for --letters")),
+            },
+            _ => positional.push(arg.to_string()),
         }
     }
-    None
+    layout.row_multiplier = multiplier.unwrap_or(layout.cols);
+    layout.validate()?;
+    Ok((layout, positional))
 }
 
 fn main() {
-    let input = fs::read_to_string("./day-05/input.txt").unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (layout, positional) = match parse_layout(&args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    if let Some((command, rest)) = positional.split_first() {
+        // Prints an occupancy map and summary of a manifest of passes
+        if command == "map" {
+            let input =
+                fs::read_to_string(rest.first().map_or("./day-05/input.txt", String::as_str))
+                    .unwrap();
+            let passes: Vec<&str> = input.lines().collect();
+            let seat_map = SeatMap::from_passes(&layout, &passes).unwrap();
+            print!("{}", seat_map.render());
+            println!("Empty seats: {}", seat_map.empty_seats().len());
+            println!("Empty blocks: {}", seat_map.empty_blocks().len());
+            println!("Empty rows: {:?}", seat_map.empty_rows());
+            println!("Missing seat ids: {:?}", seat_map.missing_seat_ids());
+            println!("Duplicate passes: {:?}", seat_map.duplicates);
+            return;
+        }
+        // Seats each `name:size[:window|aisle]` party around the passes
+        // already taken in a manifest
+        if command == "allocate" {
+            let (path, parties) = rest.split_first().unwrap();
+            let input = fs::read_to_string(path).unwrap();
+            let taken: Vec<&str> = input.lines().collect();
+            let mut seat_map = SeatMap::from_passes(&layout, &taken).unwrap();
+            let parties: Vec<Party> = parties.iter().map(|p| p.parse().unwrap()).collect();
+            match allocate::allocate_seats(&mut seat_map, &parties) {
+                Ok(assignments) => {
+                    for assignment in assignments {
+                        println!("{}: {}", assignment.party, assignment.passes.join(" "));
+                    }
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        // Prints passes for each seat id or `row,col` pair given
+        if command == "encode" {
+            for seat in rest {
+                let pass = match seat.split_once(',') {
+                    Some((row, col)) => {
+                        encode_ticket_seat(&layout, row.parse().unwrap(), col.parse().unwrap())
+                    }
+                    None => encode_ticket_seat_id(&layout, seat.parse().unwrap()),
+                };
+                println!("{}", pass.unwrap());
+            }
+            return;
+        }
+    }
+
+    let input = fs::read_to_string(
+        positional
+            .first()
+            .map_or("./day-05/input.txt", String::as_str),
+    )
+    .unwrap();
 
     let max_idx = input
-        .split('\n')
-        .map(|ticket| calculate_ticket_seat_id(128, 8, ticket))
+        .lines()
+        .map(|ticket| calculate_ticket_seat_id(&layout, ticket).unwrap())
         .max()
         .unwrap();
 
@@ -64,7 +298,7 @@ fn main() {
 
     println!(
         "Part 2: {}",
-        find_missing_seat_id(128, 8, input.split('\n').collect()).unwrap()
+        find_missing_seat_id(&layout, input.lines().collect()).unwrap()
     );
 }
 
@@ -72,13 +306,107 @@ fn main() {
 mod test {
     use super::*;
 
+    use proptest::prelude::*;
+
     #[test]
     fn test_sample_1() {
-        assert_eq!(calculate_seat_idx(128, "FBFBBFF"), 44);
-        assert_eq!(calculate_seat_idx(8, "RLR"), 5);
-        assert_eq!(calculate_ticket_seat_id(128, 8, "FBFBBFFRLR"), 357);
-        assert_eq!(calculate_ticket_seat_id(128, 8, "BFFFBBFRRR"), 567);
-        assert_eq!(calculate_ticket_seat_id(128, 8, "FFFBBBFRRR"), 119);
-        assert_eq!(calculate_ticket_seat_id(128, 8, "BBFFBBFRLL"), 820);
+        let layout = SeatLayout::default();
+        assert_eq!(calculate_seat_idx(128, ('F', 'B'), "FBFBBFF"), Ok(44));
+        assert_eq!(calculate_seat_idx(8, ('L', 'R'), "RLR"), Ok(5));
+        assert_eq!(calculate_ticket_seat_id(&layout, "FBFBBFFRLR"), Ok(357));
+        assert_eq!(calculate_ticket_seat_id(&layout, "BFFFBBFRRR"), Ok(567));
+        assert_eq!(calculate_ticket_seat_id(&layout, "FFFBBBFRRR"), Ok(119));
+        assert_eq!(calculate_ticket_seat_id(&layout, "BBFFBBFRLL"), Ok(820));
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let layout = SeatLayout {
+            rows: 256,
+            cols: 12,
+            row_multiplier: 12,
+            row_letters: ('A', 'Z'),
+            col_letters: ('<', '>'),
+        };
+        assert_eq!(layout.pass_len(), 12);
+        assert_eq!(
+            calculate_ticket_seat(&layout, "ZAAAAAAZ>0>>"),
+            Err(PassError::Letter('0'))
+        );
+        assert_eq!(
+            calculate_ticket_seat(&layout, "ZAAAAAAZ><>>"),
+            Ok((129, 11))
+        );
+        assert_eq!(calculate_ticket_seat_id(&layout, "ZAAAAAAZ><>>"), Ok(1559));
+        assert_eq!(
+            calculate_ticket_seat_id(&layout, "ZAAAAAAZ>>>>"),
+            Err(PassError::OutOfRange(15))
+        );
+        assert_eq!(
+            calculate_ticket_seat_id(&layout, "FBFBBFFRLR"),
+            Err(PassError::Length {
+                expected: 12,
+                found: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<String>>();
+        let (layout, positional) = parse_layout(&args("map --rows 4 --letters AZ<> x")).unwrap();
+        assert_eq!((layout.rows, layout.col_letters), (4, ('<', '>')));
+        assert_eq!(positional, vec!["map", "x"]);
+        assert!(parse_layout(&args("--letters FBL")).is_err());
+        assert!(parse_layout(&args("--letters FFLR")).is_err());
+        assert!(parse_layout(&args("--rows 0")).is_err());
+        assert!(parse_layout(&args("--cols x")).is_err());
+        assert!(parse_layout(&args("--multiplier")).is_err());
+    }
+
+    #[test]
+    fn test_encode_every_seat() {
+        let layout = SeatLayout::default();
+        assert_eq!(
+            encode_ticket_seat_id(&layout, 357),
+            Ok("FBFBBFFRLR".to_string())
+        );
+        assert_eq!(
+            encode_ticket_seat(&layout, 70, 7),
+            Ok("BFFFBBFRRR".to_string())
+        );
+        assert_eq!(
+            encode_ticket_seat(&layout, 128, 0),
+            Err(PassError::OutOfRange(128))
+        );
+        for seat_id in 0..(layout.rows * layout.cols) {
+            let pass = encode_ticket_seat_id(&layout, seat_id).unwrap();
+            assert_eq!(calculate_ticket_seat_id(&layout, &pass), Ok(seat_id));
+        }
+    }
+
+    fn layout_strategy() -> impl Strategy<Value = SeatLayout> {
+        (1..1024i64, 1..32i64, 0..4i64).prop_map(|(rows, cols, extra)| SeatLayout {
+            rows,
+            cols,
+            row_multiplier: cols + extra,
+            row_letters: ('D', 'U'),
+            col_letters: ('W', 'E'),
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_encode_decode_round_trip(
+            (layout, row, col) in layout_strategy()
+                .prop_flat_map(|l| (Just(l.clone()), 0..l.rows, 0..l.cols))
+        ) {
+            let pass = encode_ticket_seat(&layout, row, col).unwrap();
+            prop_assert_eq!(pass.len(), layout.pass_len());
+            prop_assert_eq!(calculate_ticket_seat(&layout, &pass), Ok((row, col)));
+
+            let seat_id = layout.seat_id(row, col);
+            prop_assert_eq!(encode_ticket_seat_id(&layout, seat_id), Ok(pass));
+        }
     }
 }
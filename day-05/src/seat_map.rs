@@ -0,0 +1,154 @@
+use super::{calculate_ticket_seat, PassError, SeatLayout};
+
+/// A seat as `(row, col)`
+pub type Seat = (i64, i64);
+
+/// Occupancy of every seat on a plane, built once from a list of passes
+#[derive(Debug, Clone)]
+pub struct SeatMap {
+    pub layout: SeatLayout,
+    occupied: Vec<bool>,
+    /// Passes for seats that an earlier pass had already taken
+    pub duplicates: Vec<String>,
+}
+
+impl SeatMap {
+    pub fn from_passes<S: AsRef<str>>(
+        layout: &SeatLayout,
+        passes: &[S],
+    ) -> Result<Self, PassError> {
+        let mut seat_map = SeatMap {
+            layout: layout.clone(),
+            occupied: vec![false; (layout.rows * layout.cols) as usize],
+            duplicates: vec![],
+        };
+        for pass in passes {
+            let (row, col) = calculate_ticket_seat(layout, pass.as_ref())?;
+            if seat_map.is_occupied(row, col) {
+                seat_map.duplicates.push(pass.as_ref().to_string());
+            }
+            seat_map.occupy(row, col);
+        }
+        Ok(seat_map)
+    }
+
+    fn idx(&self, row: i64, col: i64) -> usize {
+        (row * self.layout.cols + col) as usize
+    }
+
+    pub fn is_occupied(&self, row: i64, col: i64) -> bool {
+        self.occupied[self.idx(row, col)]
+    }
+
+    pub fn occupy(&mut self, row: i64, col: i64) {
+        let idx = self.idx(row, col);
+        self.occupied[idx] = true;
+    }
+
+    /// Every seat in row-major order
+    fn seats(&self) -> impl Iterator<Item = Seat> {
+        let cols = self.layout.cols;
+        (0..self.layout.rows).flat_map(move |row| (0..cols).map(move |col| (row, col)))
+    }
+
+    pub fn empty_seats(&self) -> Vec<Seat> {
+        self.seats()
+            .filter(|(row, col)| !self.is_occupied(*row, *col))
+            .collect()
+    }
+
+    /// Runs of consecutive empty seats in row-major order, which can wrap
+    /// from the end of one row onto the start of the next
+    pub fn empty_blocks(&self) -> Vec<Vec<Seat>> {
+        let mut blocks: Vec<Vec<Seat>> = vec![];
+        let mut in_block = false;
+        for (row, col) in self.seats() {
+            if self.is_occupied(row, col) {
+                in_block = false;
+            } else if in_block {
+                blocks.last_mut().unwrap().push((row, col));
+            } else {
+                blocks.push(vec![(row, col)]);
+                in_block = true;
+            }
+        }
+        blocks
+    }
+
+    pub fn empty_rows(&self) -> Vec<i64> {
+        (0..self.layout.rows)
+            .filter(|row| (0..self.layout.cols).all(|col| !self.is_occupied(*row, col)))
+            .collect()
+    }
+
+    /// Ids of single empty seats with occupied seats on either side
+    pub fn missing_seat_ids(&self) -> Vec<i64> {
+        let last_seat = (self.layout.rows - 1, self.layout.cols - 1);
+        self.empty_blocks()
+            .iter()
+            .filter(|block| block.len() == 1 && block[0] != (0, 0) && block[0] != last_seat)
+            .map(|block| self.layout.seat_id(block[0].0, block[0].1))
+            .collect()
+    }
+
+    /// Draws one line per row, with `#` for occupied and `.` for empty seats
+    pub fn render(&self) -> String {
+        let width = (self.layout.rows - 1).to_string().len();
+        (0..self.layout.rows)
+            .map(|row| {
+                let seats: String = (0..self.layout.cols)
+                    .map(|col| if self.is_occupied(row, col) { '#' } else { '.' })
+                    .collect();
+                format!("{:>width$} {}\n", row, seats, width = width)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::encode_ticket_seat;
+    use super::*;
+
+    fn small_layout() -> SeatLayout {
+        SeatLayout {
+            rows: 4,
+            cols: 4,
+            row_multiplier: 4,
+            ..SeatLayout::default()
+        }
+    }
+
+    #[test]
+    fn test_seat_map_analysis() {
+        let layout = small_layout();
+        let passes: Vec<String> = [(1, 0), (1, 1), (1, 3), (2, 0), (2, 3), (1, 1)]
+            .iter()
+            .map(|(row, col)| encode_ticket_seat(&layout, *row, *col).unwrap())
+            .collect();
+        let seat_map = SeatMap::from_passes(&layout, &passes).unwrap();
+
+        assert_eq!(seat_map.duplicates, vec![passes[1].clone()]);
+        assert_eq!(seat_map.empty_seats().len(), 11);
+        assert_eq!(seat_map.empty_rows(), vec![0, 3]);
+        assert_eq!(
+            seat_map.empty_blocks(),
+            vec![
+                vec![(0, 0), (0, 1), (0, 2), (0, 3)],
+                vec![(1, 2)],
+                vec![(2, 1), (2, 2)],
+                vec![(3, 0), (3, 1), (3, 2), (3, 3)],
+            ]
+        );
+        assert_eq!(seat_map.missing_seat_ids(), vec![6]);
+        assert_eq!(seat_map.render(), "0 ....\n1 ##.#\n2 #..#\n3 ....\n");
+    }
+
+    #[test]
+    fn test_seat_map_invalid_pass() {
+        assert_eq!(
+            SeatMap::from_passes(&small_layout(), &["FFLX"]).unwrap_err(),
+            PassError::Letter('X')
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::str::FromStr;
+
+/// Set of questions answered yes, stored as one bit per question id
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnswerSet(Vec<u64>);
+
+impl AnswerSet {
+    pub fn from_ids<I: IntoIterator<Item = usize>>(ids: I) -> Self {
+        let mut set = AnswerSet::default();
+        for id in ids {
+            set.insert(id);
+        }
+        set
+    }
+
+    pub fn insert(&mut self, id: usize) {
+        if self.0.len() <= id / 64 {
+            self.0.resize(id / 64 + 1, 0);
+        }
+        self.0[id / 64] |= 1 << (id % 64);
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.0
+            .get(id / 64)
+            .is_some_and(|word| word & 1 << (id % 64) != 0)
+    }
+
+    /// Combines the words of both sets, dropping trailing empty words so
+    /// equal sets always compare equal
+    fn combine<F: Fn(u64, u64) -> u64>(&self, other: &Self, f: F) -> Self {
+        let len = self.0.len().max(other.0.len());
+        let mut words: Vec<u64> = (0..len)
+            .map(|idx| {
+                f(
+                    self.0.get(idx).copied().unwrap_or(0),
+                    other.0.get(idx).copied().unwrap_or(0),
+                )
+            })
+            .collect();
+        while words.last() == Some(&0) {
+            words.pop();
+        }
+        AnswerSet(words)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a & b)
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a ^ b)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn ids(&self) -> Vec<usize> {
+        (0..self.0.len() * 64)
+            .filter(|id| self.contains(*id))
+            .collect()
+    }
+}
+
+/// Question about a group, answered with the set of questions it applies to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Query {
+    Anyone,
+    Everyone,
+    /// Questions answered by an odd number of people
+    SymmetricDifference,
+    AtLeast(usize),
+    Exactly(usize),
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let count = |n: &str| {
+            n.parse::<usize>()
+                .map_err(|_| format!("Invalid count {}", n))
+        };
+        match s.split_once(':') {
+            None if s == "anyone" => Ok(Self::Anyone),
+            None if s == "everyone" => Ok(Self::Everyone),
+            None if s == "exactly-one" => Ok(Self::Exactly(1)),
+            None if s == "symmetric-difference" => Ok(Self::SymmetricDifference),
+            Some(("at-least", n)) => Ok(Self::AtLeast(count(n)?)),
+            Some(("exactly", n)) => Ok(Self::Exactly(count(n)?)),
+            _ => Err(format!("Unknown query {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub people: Vec<AnswerSet>,
+}
+
+impl Group {
+    pub fn anyone(&self) -> AnswerSet {
+        self.people
+            .iter()
+            .fold(AnswerSet::default(), |acc, p| acc.union(p))
+    }
+
+    pub fn everyone(&self) -> AnswerSet {
+        self.people
+            .iter()
+            .fold(self.anyone(), |acc, p| acc.intersection(p))
+    }
+
+    pub fn symmetric_difference(&self) -> AnswerSet {
+        self.people
+            .iter()
+            .fold(AnswerSet::default(), |acc, p| acc.symmetric_difference(p))
+    }
+
+    /// Number of people who answered yes to each question, indexed by id
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![];
+        for id in self.people.iter().flat_map(AnswerSet::ids) {
+            if counts.len() <= id {
+                counts.resize(id + 1, 0);
+            }
+            counts[id] += 1;
+        }
+        counts
+    }
+
+    fn matching_counts<F: Fn(usize) -> bool>(&self, f: F) -> AnswerSet {
+        AnswerSet::from_ids(
+            self.counts()
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| f(**count))
+                .map(|(id, _)| id),
+        )
+    }
+
+    pub fn at_least(&self, k: usize) -> AnswerSet {
+        self.matching_counts(|count| count >= k && count > 0)
+    }
+
+    pub fn exactly(&self, k: usize) -> AnswerSet {
+        self.matching_counts(|count| count == k && count > 0)
+    }
+
+    pub fn query(&self, query: Query) -> AnswerSet {
+        match query {
+            Query::Anyone => self.anyone(),
+            Query::Everyone => self.everyone(),
+            Query::SymmetricDifference => self.symmetric_difference(),
+            Query::AtLeast(k) => self.at_least(k),
+            Query::Exactly(k) => self.exactly(k),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_answer_set() {
+        let a = AnswerSet::from_ids(vec![0, 1, 2, 100]);
+        let b = AnswerSet::from_ids(vec![1, 2, 3]);
+        assert_eq!(a.len(), 4);
+        assert_eq!(a.union(&b).ids(), vec![0, 1, 2, 3, 100]);
+        assert_eq!(a.intersection(&b).ids(), vec![1, 2]);
+        assert_eq!(a.intersection(&b), AnswerSet::from_ids(vec![1, 2]));
+        assert_eq!(a.symmetric_difference(&b).ids(), vec![0, 3, 100]);
+        assert!(!a.contains(99));
+        assert!(!a.contains(1000));
+    }
+
+    #[test]
+    fn test_group_queries() {
+        let group = Group {
+            people: vec![
+                AnswerSet::from_ids(vec![0, 1]),
+                AnswerSet::from_ids(vec![0, 2]),
+                AnswerSet::from_ids(vec![0, 1, 3]),
+                AnswerSet::from_ids(vec![0]),
+            ],
+        };
+        assert_eq!(group.query(Query::Anyone).ids(), vec![0, 1, 2, 3]);
+        assert_eq!(group.query(Query::Everyone).ids(), vec![0]);
+        assert_eq!(group.query(Query::AtLeast(2)).ids(), vec![0, 1]);
+        assert_eq!(group.query(Query::Exactly(1)).ids(), vec![2, 3]);
+        assert_eq!(group.query(Query::Exactly(0)).len(), 0);
+        assert_eq!(group.query(Query::SymmetricDifference).ids(), vec![2, 3]);
+        assert_eq!("at-least:3".parse(), Ok(Query::AtLeast(3)));
+        assert_eq!("exactly-one".parse(), Ok(Query::Exactly(1)));
+        assert!("most".parse::<Query>().is_err());
+    }
+}
@@ -1,40 +1,133 @@
+use std::env;
 use std::fs;
-
-fn unique_answers(answers: &str) -> Vec<char> {
-    let mut all_answers: Vec<char> = answers
-        .split('\n')
-        .flat_map(|s| s.chars())
-        .collect::<Vec<char>>();
-    all_answers.sort_unstable();
-    all_answers.dedup();
-    all_answers
+use std::str::FromStr;
+
+mod group;
+mod stats;
+mod survey;
+
+use group::Query;
+use stats::Report;
+use survey::{Alphabet, Survey};
+
+/// Removes a `--flag value` pair from the arguments, returning the value if
+/// the flag is present, or an error if it has no value
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let idx = match args.iter().position(|arg| arg == flag) {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+    args.remove(idx);
+    if idx == args.len() {
+        return Err(format!("Missing a value for {}", flag));
+    }
+    Ok(Some(args.remove(idx)))
 }
 
-fn answers_for_group_every(answers: &str) -> usize {
-    let num_answers = answers.split('\n').count();
-    let unique_answer_chars = unique_answers(answers);
-    unique_answer_chars
-        .iter()
-        .filter(|u_a| answers.split('\n').filter(|a| a.contains(**u_a)).count() == num_answers)
-        .count()
+/// Reads the `--alphabet` and `--input` flags
+fn parse_flags(args: &mut Vec<String>) -> Result<(Alphabet, String), String> {
+    let alphabet = match take_flag(args, "--alphabet")? {
+        Some(a) => Alphabet::from_str(&a)?,
+        None => Alphabet::Lowercase,
+    };
+    let path = take_flag(args, "--input")?.unwrap_or_else(|| "./day-06/input.txt".to_string());
+    Ok((alphabet, path))
 }
 
 fn main() {
-    let input = fs::read_to_string("./day-06/input.txt").unwrap();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let (alphabet, path) = match parse_flags(&mut args) {
+        Ok(flags) => flags,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    let input = fs::read_to_string(path).unwrap();
+    let survey = match Survey::parse(&input, alphabet) {
+        Ok(survey) => survey,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        // Per-question or group size statistics, as a table or CSV
+        [report @ ("questions" | "sizes"), rest @ ..] => {
+            let report_data = Report::new(&survey);
+            let csv = rest.first() == Some(&"csv");
+            print!(
+                "{}",
+                match (*report, csv) {
+                    ("questions", false) => report_data.questions_table(),
+                    ("questions", true) => report_data.questions_csv(),
+                    (_, false) => report_data.group_sizes_table(),
+                    (_, true) => report_data.group_sizes_csv(),
+                }
+            );
+            return;
+        }
+        // Lists the questions matching a query in each group
+        [query, "list"] => {
+            let query = match Query::from_str(query) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            for (idx, group) in survey.groups.iter().enumerate() {
+                println!(
+                    "{}: {}",
+                    idx + 1,
+                    survey.names(&group.query(query)).join(",")
+                );
+            }
+            return;
+        }
+        // Any other query is summed across all groups, e.g. `at-least:2`
+        [query] => {
+            let query = match Query::from_str(query) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            println!(
+                "{}",
+                survey
+                    .groups
+                    .iter()
+                    .map(|g| g.query(query).len())
+                    .sum::<usize>()
+            );
+            return;
+        }
+        _ => {}
+    }
 
     println!(
         "Part 1: {}",
-        input
-            .split("\n\n")
-            .map(|g| unique_answers(g).len())
+        survey
+            .groups
+            .iter()
+            .map(|g| g.anyone().len())
             .sum::<usize>()
     );
 
     println!(
         "Part 2: {}",
-        input
-            .split("\n\n")
-            .map(answers_for_group_every)
+        survey
+            .groups
+            .iter()
+            .map(|g| g.everyone().len())
             .sum::<usize>()
     );
 }
@@ -43,6 +136,10 @@ fn main() {
 mod test {
     use super::*;
 
+    fn parse_groups(answers: &str) -> Vec<group::Group> {
+        Survey::parse(answers, Alphabet::Lowercase).unwrap().groups
+    }
+
     #[test]
     fn test_answers_for_group_any() {
         let answers = "abc
@@ -60,8 +157,12 @@ a
 a
 
 b";
-        let answers_any: usize = answers.split("\n\n").map(|g| unique_answers(g).len()).sum();
-        assert_eq!(unique_answers("abbc"), vec!['a', 'b', 'c']);
+        let answers_any: usize = parse_groups(answers).iter().map(|g| g.anyone().len()).sum();
+        let survey = Survey::parse("abbc", Alphabet::Lowercase).unwrap();
+        assert_eq!(
+            survey.names(&survey.groups[0].anyone()),
+            vec!["a", "b", "c"]
+        );
         assert_eq!(answers_any, 11);
     }
 
@@ -85,11 +186,21 @@ b";
         let answer_group_0 = "a
 b
 c";
-        let answers_every: usize = answers
-            .split("\n\n")
-            .map(|g| answers_for_group_every(g))
+        let answers_every: usize = parse_groups(answers)
+            .iter()
+            .map(|g| g.everyone().len())
             .sum();
-        assert_eq!(answers_for_group_every(answer_group_0), 0);
+        assert_eq!(parse_groups(answer_group_0)[0].everyone().len(), 0);
         assert_eq!(answers_every, 6);
     }
+
+    #[test]
+    fn test_messy_separators() {
+        let answers = "abc\r\nab\r\n\r\n \r\n\r\nb\r\n\r\n\r\n";
+        let groups = parse_groups(answers);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].people.len(), 2);
+        assert_eq!(groups[0].everyone().len(), 2);
+        assert_eq!(groups[1].everyone().len(), 1);
+    }
 }
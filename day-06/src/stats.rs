@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use super::survey::Survey;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuestionStats {
+    pub question: String,
+    /// People who answered yes
+    pub people: usize,
+    /// Groups where anyone answered yes
+    pub groups: usize,
+    /// Groups where everyone answered yes
+    pub unanimous: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub questions: Vec<QuestionStats>,
+    /// Number of groups of each size
+    pub group_sizes: BTreeMap<usize, usize>,
+}
+
+impl Report {
+    pub fn new(survey: &Survey) -> Self {
+        let (ids, mut questions): (Vec<usize>, Vec<QuestionStats>) = survey
+            .questions()
+            .into_iter()
+            .map(|(id, question)| {
+                let stats = QuestionStats {
+                    question: question.to_string(),
+                    people: 0,
+                    groups: 0,
+                    unanimous: 0,
+                };
+                (id, stats)
+            })
+            .unzip();
+        // Each group's totals are worked out once rather than per question
+        for group in survey.groups.iter() {
+            let (counts, anyone, everyone) = (group.counts(), group.anyone(), group.everyone());
+            for (id, stats) in ids.iter().zip(questions.iter_mut()) {
+                stats.people += counts.get(*id).copied().unwrap_or(0);
+                stats.groups += anyone.contains(*id) as usize;
+                stats.unanimous += everyone.contains(*id) as usize;
+            }
+        }
+
+        let mut group_sizes = BTreeMap::new();
+        for group in survey.groups.iter() {
+            *group_sizes.entry(group.people.len()).or_insert(0) += 1;
+        }
+
+        Report {
+            questions,
+            group_sizes,
+        }
+    }
+
+    pub fn questions_table(&self) -> String {
+        let width = self
+            .questions
+            .iter()
+            .map(|q| q.question.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("question".len());
+        let mut table = format!(
+            "{:<width$} {:>6} {:>6} {:>9}\n",
+            "question",
+            "people",
+            "groups",
+            "unanimous",
+            width = width
+        );
+        for q in self.questions.iter() {
+            table += &format!(
+                "{:<width$} {:>6} {:>6} {:>9}\n",
+                q.question,
+                q.people,
+                q.groups,
+                q.unanimous,
+                width = width
+            );
+        }
+        table
+    }
+
+    pub fn questions_csv(&self) -> String {
+        let mut csv = String::from("question,people,groups,unanimous\n");
+        for q in self.questions.iter() {
+            csv += &format!("{},{},{},{}\n", q.question, q.people, q.groups, q.unanimous);
+        }
+        csv
+    }
+
+    pub fn group_sizes_table(&self) -> String {
+        let mut table = format!("{:<10} {:>6}\n", "group_size", "groups");
+        for (size, count) in self.group_sizes.iter() {
+            table += &format!("{:<10} {:>6}\n", size, count);
+        }
+        table
+    }
+
+    pub fn group_sizes_csv(&self) -> String {
+        let mut csv = String::from("group_size,groups\n");
+        for (size, count) in self.group_sizes.iter() {
+            csv += &format!("{},{}\n", size, count);
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::survey::Alphabet;
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let survey = Survey::parse(
+            "abc\n\na\nb\nc\n\nab\nac\n\na\na\na\na\n\nb",
+            Alphabet::Lowercase,
+        )
+        .unwrap();
+        let report = Report::new(&survey);
+
+        assert_eq!(
+            report.questions[0],
+            QuestionStats {
+                question: "a".to_string(),
+                people: 8,
+                groups: 4,
+                unanimous: 3,
+            }
+        );
+        assert_eq!(report.questions[25].people, 0);
+        assert_eq!(
+            report.group_sizes.into_iter().collect::<Vec<_>>(),
+            vec![(1, 2), (2, 1), (3, 1), (4, 1)]
+        );
+    }
+
+    #[test]
+    fn test_report_output() {
+        let report = Report::new(&Survey::parse("ab\nb", Alphabet::Lowercase).unwrap());
+        assert!(report
+            .questions_csv()
+            .starts_with("question,people,groups,unanimous\na,1,1,0\nb,2,1,1\nc,0,0,0\n"));
+        assert_eq!(report.group_sizes_csv(), "group_size,groups\n2,1\n");
+        assert_eq!(
+            report.group_sizes_table(),
+            "group_size groups\n2               1\n"
+        );
+        assert_eq!(report.questions_table().lines().count(), 27);
+    }
+
+    #[test]
+    fn test_report_codes() {
+        let survey = Survey::parse("pets,smoker\npets\n\nsmoker", Alphabet::Codes).unwrap();
+        let report = Report::new(&survey);
+        assert_eq!(
+            report.questions_csv(),
+            "question,people,groups,unanimous\npets,2,1,1\nsmoker,2,2,1\n"
+        );
+    }
+}
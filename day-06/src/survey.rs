@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use records::records;
+
+use super::group::{AnswerSet, Group};
+
+/// How a person's line of answers is split into questions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alphabet {
+    /// Single letters from a to z, as in the puzzle input
+    Lowercase,
+    /// Any single Unicode letter
+    Letters,
+    /// Comma-separated codes made of letters, digits, `-` and `_`
+    Codes,
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "a-z" => Ok(Self::Lowercase),
+            "letters" => Ok(Self::Letters),
+            "codes" => Ok(Self::Codes),
+            _ => Err(format!("Unknown alphabet {}", s)),
+        }
+    }
+}
+
+impl Alphabet {
+    fn questions<'a>(&self, answers: &'a str) -> Vec<&'a str> {
+        let answers = answers.trim();
+        match self {
+            Self::Lowercase | Self::Letters => answers
+                .char_indices()
+                .map(|(idx, c)| &answers[idx..idx + c.len_utf8()])
+                .collect(),
+            Self::Codes => answers
+                .split(',')
+                .map(str::trim)
+                .filter(|code| !code.is_empty())
+                .collect(),
+        }
+    }
+
+    fn is_valid(&self, question: &str) -> bool {
+        match self {
+            Self::Lowercase => question.chars().all(|c| c.is_ascii_lowercase()),
+            Self::Letters => question.chars().all(char::is_alphabetic),
+            Self::Codes => question
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_'),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidAnswer {
+    pub person: String,
+    pub symbol: String,
+}
+
+impl fmt::Display for InvalidAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unexpected {:?} in answers {:?}",
+            self.symbol, self.person
+        )
+    }
+}
+
+/// Groups of answers along with the questions their ids refer to
+#[derive(Debug, Clone)]
+pub struct Survey {
+    pub groups: Vec<Group>,
+    questions: Vec<String>,
+    ids: HashMap<String, usize>,
+}
+
+impl Survey {
+    pub fn parse(input: &str, alphabet: Alphabet) -> Result<Self, InvalidAnswer> {
+        let mut survey = Survey {
+            groups: vec![],
+            questions: vec![],
+            ids: HashMap::new(),
+        };
+        // Every question is known upfront for a to z, which keeps the
+        // question ids in alphabetical order
+        if alphabet == Alphabet::Lowercase {
+            for c in 'a'..='z' {
+                survey.id(&c.to_string());
+            }
+        }
+
+        for people in records(input) {
+            let mut group = Group { people: vec![] };
+            for person in people {
+                let mut answers = AnswerSet::default();
+                for question in alphabet.questions(person) {
+                    if !alphabet.is_valid(question) {
+                        return Err(InvalidAnswer {
+                            person: person.to_string(),
+                            symbol: question.to_string(),
+                        });
+                    }
+                    answers.insert(survey.id(question));
+                }
+                group.people.push(answers);
+            }
+            survey.groups.push(group);
+        }
+
+        Ok(survey)
+    }
+
+    fn id(&mut self, question: &str) -> usize {
+        if let Some(id) = self.ids.get(question) {
+            return *id;
+        }
+        self.questions.push(question.to_string());
+        self.ids
+            .insert(question.to_string(), self.questions.len() - 1);
+        self.questions.len() - 1
+    }
+
+    /// Every question with its id, sorted by question
+    pub fn questions(&self) -> Vec<(usize, &str)> {
+        let mut questions: Vec<(usize, &str)> = self
+            .questions
+            .iter()
+            .enumerate()
+            .map(|(id, q)| (id, q.as_str()))
+            .collect();
+        questions.sort_unstable_by_key(|(_, q)| *q);
+        questions
+    }
+
+    pub fn names(&self, answers: &AnswerSet) -> Vec<&str> {
+        let mut names: Vec<&str> = answers
+            .ids()
+            .iter()
+            .map(|id| self.questions[*id].as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_alphabets() {
+        let survey = Survey::parse("ab\nac\n\nb", Alphabet::Lowercase).unwrap();
+        assert_eq!(survey.questions().len(), 26);
+        assert_eq!(
+            survey.names(&survey.groups[0].anyone()),
+            vec!["a", "b", "c"]
+        );
+
+        let survey = Survey::parse("äb\nçä\n\nß", Alphabet::Letters).unwrap();
+        assert_eq!(survey.names(&survey.groups[0].everyone()), vec!["ä"]);
+        assert_eq!(survey.questions().len(), 4);
+
+        let survey = Survey::parse("q1, q10,\nq10,extra_2\n\nq1", Alphabet::Codes).unwrap();
+        assert_eq!(
+            survey.names(&survey.groups[0].anyone()),
+            vec!["extra_2", "q1", "q10"]
+        );
+        assert_eq!(survey.names(&survey.groups[0].everyone()), vec!["q10"]);
+    }
+
+    #[test]
+    fn test_unexpected_symbols() {
+        assert_eq!(
+            Survey::parse("ab\na c", Alphabet::Lowercase).unwrap_err(),
+            InvalidAnswer {
+                person: "a c".to_string(),
+                symbol: " ".to_string(),
+            }
+        );
+        assert!(Survey::parse("aB", Alphabet::Lowercase).is_err());
+        assert!(Survey::parse("aB", Alphabet::Letters).is_ok());
+        assert!(Survey::parse("a1", Alphabet::Letters).is_err());
+        assert!(Survey::parse("q1;q2", Alphabet::Codes).is_err());
+    }
+}
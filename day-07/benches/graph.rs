@@ -0,0 +1,68 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use day_07::graph::BagGraph;
+
+/// Spells out `n` in letters so generated colours stay plain words
+fn word(mut n: usize) -> String {
+    let mut word = String::new();
+    loop {
+        word.insert(0, (b'a' + (n % 26) as u8) as char);
+        n /= 26;
+        if n == 0 {
+            return word;
+        }
+    }
+}
+
+/// Rules for `layers` layers of `width` colours, where each bag holds two
+/// bags from the next layer down, so the number of paths grows exponentially
+fn layered_rules(layers: usize, width: usize) -> String {
+    let mut rules = String::new();
+    for layer in 0..layers {
+        for idx in 0..width {
+            let color = format!("{} {}", word(layer), word(idx));
+            if layer + 1 == layers {
+                rules += &format!("{} bags contain no other bags.\n", color);
+            } else {
+                rules += &format!(
+                    "{} bags contain 1 {} {} bag, 2 {} {} bags.\n",
+                    color,
+                    word(layer + 1),
+                    word(idx),
+                    word(layer + 1),
+                    word((idx + 1) % width)
+                );
+            }
+        }
+    }
+    rules
+}
+
+fn bench_graph(c: &mut Criterion) {
+    let rules = layered_rules(30, 200);
+    let graph = BagGraph::parse(&rules).unwrap();
+    let top = format!("{} {}", word(0), word(0));
+    let bottom = format!("{} {}", word(29), word(0));
+
+    c.bench_function("parse 6000 rules", |b| {
+        b.iter(|| BagGraph::parse(black_box(&rules)))
+    });
+    c.bench_function("bags containing bottom layer", |b| {
+        b.iter(|| graph.bags_containing(black_box(&bottom)))
+    });
+    // Totals are cached between queries, so each run gets a fresh copy of
+    // the graph to measure the full traversal
+    c.bench_function("bags contained in top layer", |b| {
+        b.iter_batched_ref(
+            || graph.clone(),
+            |graph| graph.bags_contained(black_box(&top)),
+            BatchSize::LargeInput,
+        )
+    });
+    c.bench_function("bags contained in top layer, cached", |b| {
+        b.iter(|| graph.bags_contained(black_box(&top)))
+    });
+}
+
+criterion_group!(benches, bench_graph);
+criterion_main!(benches);
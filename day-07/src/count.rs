@@ -0,0 +1,76 @@
+use num_bigint::BigUint;
+
+/// Numbers bag totals can be counted in, where the arithmetic either succeeds
+/// or reports an overflow with `None`
+pub trait BagCount: Clone {
+    fn from_count(count: usize) -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
+
+impl BagCount for usize {
+    fn from_count(count: usize) -> Self {
+        count
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        usize::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        usize::checked_mul(*self, *other)
+    }
+}
+
+impl BagCount for BigUint {
+    fn from_count(count: usize) -> Self {
+        BigUint::from(count)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+/// Whether totals are counted in `usize`, failing on overflow, or exactly in
+/// arbitrary precision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arithmetic {
+    Checked,
+    Exact,
+}
+
+/// Sums `num * (extra + count)` over `(num, extra, count)` terms, the total
+/// for a bag holding `num` bags that each add `extra` to the total on top of
+/// their own `count`
+pub fn sum_terms<'a, T, I>(terms: I) -> Option<T>
+where
+    T: BagCount + 'a,
+    I: IntoIterator<Item = (usize, usize, &'a T)>,
+{
+    terms
+        .into_iter()
+        .try_fold(T::from_count(0), |total, (num, extra, count)| {
+            let term = T::from_count(num).checked_mul(&T::from_count(extra).checked_add(count)?)?;
+            total.checked_add(&term)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sum_terms() {
+        assert_eq!(sum_terms(vec![(2, 1, &3), (4, 0, &5)]), Some(2 * 4 + 4 * 5));
+        assert_eq!(sum_terms::<usize, _>(vec![(2, 1, &usize::MAX)]), None);
+        assert_eq!(
+            sum_terms(vec![(2, 1, &BigUint::from(usize::MAX))]),
+            Some(BigUint::from(usize::MAX) * 2u32 + 2u32)
+        );
+    }
+}
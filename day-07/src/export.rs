@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use super::graph::{BagGraph, Direction};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            _ => Err(format!("Unknown format {}", s)),
+        }
+    }
+}
+
+fn quote(color: &str, format: Format) -> String {
+    let escaped = match format {
+        Format::Dot => color.replace('"', "\\\""),
+        Format::Mermaid => color.replace('"', "#quot;"),
+    };
+    format!("\"{}\"", escaped)
+}
+
+/// Draws the rules as a graph with an edge from each bag to the bags directly
+/// inside it, labelled with the count. When `focus` is set only the bags
+/// reachable from that bag in that direction are drawn.
+pub fn export(graph: &BagGraph, format: Format, focus: Option<(usize, Direction)>) -> String {
+    let included = match focus {
+        Some((id, direction)) => graph.reachable(id, direction),
+        None => vec![true; graph.id_bound()],
+    };
+    let ids: Vec<usize> = graph.ids().filter(|id| included[*id]).collect();
+
+    let mut out = String::from(match format {
+        Format::Dot => "digraph bags {\n",
+        Format::Mermaid => "graph TD\n",
+    });
+    for id in ids.iter() {
+        out += &match format {
+            Format::Dot => format!("    {};\n", quote(graph.name(*id), format)),
+            Format::Mermaid => format!("    n{}[{}]\n", id, quote(graph.name(*id), format)),
+        };
+    }
+    for id in ids.iter() {
+        for (child, num) in graph.contains(*id).iter().filter(|(c, _)| included[*c]) {
+            out += &match format {
+                Format::Dot => format!(
+                    "    {} -> {} [label=\"{}\"];\n",
+                    quote(graph.name(*id), format),
+                    quote(graph.name(*child), format),
+                    num
+                ),
+                Format::Mermaid => format!("    n{} -->|{}| n{}\n", id, num, child),
+            };
+        }
+    }
+    if format == Format::Dot {
+        out += "}\n";
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> BagGraph {
+        BagGraph::parse(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags.
+shiny gold bags contain 3 faded blue bags.
+faded blue bags contain no other bags.",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_export_dot() {
+        let graph = sample();
+        let gold = graph.id("shiny gold").unwrap();
+        assert_eq!(
+            export(&graph, Format::Dot, Some((gold, Direction::Down))),
+            "digraph bags {
+    \"shiny gold\";
+    \"faded blue\";
+    \"shiny gold\" -> \"faded blue\" [label=\"3\"];
+}
+"
+        );
+        assert_eq!(export(&graph, Format::Dot, None).lines().count(), 2 + 5 + 5);
+    }
+
+    #[test]
+    fn test_export_mermaid() {
+        let graph = sample();
+        let white = graph.id("bright white").unwrap();
+        assert_eq!(
+            export(&graph, Format::Mermaid, Some((white, Direction::Up))),
+            "graph TD
+    n0[\"light red\"]
+    n1[\"bright white\"]
+    n0 -->|1| n1
+"
+        );
+    }
+}
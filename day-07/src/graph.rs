@@ -0,0 +1,569 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+
+use super::count::{sum_terms, BagCount};
+use super::parse::{parse_rule, ParseError, Rule};
+
+/// Bag rules parsed into a graph, with each colour interned as an id
+#[derive(Debug, Clone, Default)]
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    /// Bags directly inside each bag, as `(id, count)`
+    contains: Vec<Vec<(usize, usize)>>,
+    /// Bags directly holding each bag, as `(id, count)`
+    contained_by: Vec<Vec<(usize, usize)>>,
+    /// Whether each colour has its own rule, rather than only being named
+    /// inside another bag's
+    has_rule: Vec<bool>,
+    /// Colours with rules in the order the rules were added
+    rule_order: Vec<usize>,
+    /// Total bags inside each bag from earlier queries, cleared for a bag and
+    /// everything holding it whenever its rule changes
+    totals: RefCell<Vec<Option<usize>>>,
+}
+
+impl BagGraph {
+    /// Parses one rule per line, skipping blank lines. Fails on the first
+    /// malformed rule or on a second rule for the same colour.
+    pub fn parse(rules: &str) -> Result<Self, ParseError> {
+        let mut graph = BagGraph::default();
+
+        for (idx, line) in rules.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let error = |message| ParseError {
+                line: idx + 1,
+                message,
+            };
+            let rule = parse_rule(line).map_err(error)?;
+            if graph.id(&rule.color).is_some_and(|id| graph.has_rule[id]) {
+                return Err(error(format!("Duplicate rule for {}", rule.color)));
+            }
+            graph.set_rule(&rule);
+        }
+
+        Ok(graph)
+    }
+
+    /// Adds the rule for `rule.color`, replacing any rule it already had
+    pub fn set_rule(&mut self, rule: &Rule) {
+        let parent = self.intern(&rule.color);
+        self.clear_contents(parent);
+        if !self.has_rule[parent] {
+            self.has_rule[parent] = true;
+            self.rule_order.push(parent);
+        }
+        for (num, color) in rule.contents.iter() {
+            let child = self.intern(color);
+            self.contains[parent].push((child, *num));
+            self.contained_by[child].push((parent, *num));
+        }
+        self.invalidate(parent);
+    }
+
+    /// Removes the rule for `color`, returning whether it had one. The colour
+    /// keeps its id, but is skipped by `ids` unless another rule names it.
+    pub fn remove_rule(&mut self, color: &str) -> bool {
+        let id = match self.id(color) {
+            Some(id) if self.has_rule[id] => id,
+            _ => return false,
+        };
+        self.clear_contents(id);
+        self.has_rule[id] = false;
+        self.rule_order.retain(|bag| *bag != id);
+        self.invalidate(id);
+        true
+    }
+
+    /// The rule for the bag `id`, if it has one
+    pub fn rule(&self, id: usize) -> Option<Rule> {
+        if !self.has_rule[id] {
+            return None;
+        }
+        Some(Rule {
+            color: self.name(id).to_string(),
+            contents: self
+                .contains(id)
+                .iter()
+                .map(|(child, num)| (*num, self.name(*child).to_string()))
+                .collect(),
+        })
+    }
+
+    fn clear_contents(&mut self, id: usize) {
+        for (child, _) in mem::take(&mut self.contains[id]) {
+            self.contained_by[child].retain(|(parent, _)| *parent != id);
+        }
+    }
+
+    fn invalidate(&mut self, id: usize) {
+        let stale = self.reachable(id, Direction::Up);
+        for (total, stale) in self.totals.get_mut().iter_mut().zip(stale) {
+            if stale {
+                *total = None;
+            }
+        }
+    }
+
+    fn intern(&mut self, color: &str) -> usize {
+        if let Some(id) = self.ids.get(color) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(color.to_string());
+        self.ids.insert(color.to_string(), id);
+        self.contains.push(vec![]);
+        self.contained_by.push(vec![]);
+        self.has_rule.push(false);
+        self.totals.get_mut().push(None);
+        id
+    }
+
+    /// Id of `color` if it has a rule or is inside another bag's rule
+    pub fn id(&self, color: &str) -> Option<usize> {
+        self.ids.get(color).copied().filter(|id| self.in_use(*id))
+    }
+
+    fn in_use(&self, id: usize) -> bool {
+        self.has_rule[id] || !self.contained_by[id].is_empty()
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    /// Number of colours with a rule or inside another bag's rule
+    pub fn len(&self) -> usize {
+        self.ids().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids().next().is_none()
+    }
+
+    /// One more than the largest id, including colours whose rules were
+    /// removed, for sizing tables indexed by id
+    pub fn id_bound(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Ids of colours with a rule or inside another bag's rule
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.id_bound()).filter(move |id| self.in_use(*id))
+    }
+
+    pub fn contains(&self, id: usize) -> &[(usize, usize)] {
+        &self.contains[id]
+    }
+
+    pub fn contained_by(&self, id: usize) -> &[(usize, usize)] {
+        &self.contained_by[id]
+    }
+
+    /// Every colour that can eventually hold a bag of `color`, sorted
+    pub fn bags_containing(&self, color: &str) -> Result<Vec<String>, Cycle> {
+        let id = match self.id(color) {
+            Some(id) => id,
+            None => return Ok(vec![]),
+        };
+        let mut colors: Vec<String> = self
+            .order(id, Direction::Up)?
+            .into_iter()
+            .filter(|parent| *parent != id)
+            .map(|parent| self.name(parent).to_string())
+            .collect();
+        colors.sort_unstable();
+        Ok(colors)
+    }
+
+    /// Total number of bags inside a single bag of `color`, failing rather
+    /// than overflowing
+    pub fn bags_contained(&self, color: &str) -> Result<usize, CountError> {
+        let id = match self.id(color) {
+            Some(id) => id,
+            None => return Ok(0),
+        };
+        let mut totals = self.totals.borrow_mut();
+        // Bags with a total from an earlier query are treated as already
+        // walked, so only bags changed since then are counted again
+        let mut visits: Vec<Visit> = totals
+            .iter()
+            .map(|t| if t.is_some() { Visit::Done } else { Visit::New })
+            .collect();
+        let mut order = vec![];
+        self.walk(id, Direction::Down, &mut visits, &mut order)?;
+        self.fill_counts(order, None, &mut totals)?;
+        Ok(totals[id].unwrap())
+    }
+
+    /// Fills in the count for each bag in `order`, which must come after the
+    /// bags it holds, from the counts already filled in for those
+    fn fill_counts<T: BagCount>(
+        &self,
+        order: Vec<usize>,
+        inner: Option<usize>,
+        counts: &mut [Option<T>],
+    ) -> Result<(), CountError> {
+        for bag in order {
+            let terms = self.contains(bag).iter().map(|(child, num)| {
+                let extra = inner.map_or(1, |inner| (*child == inner) as usize);
+                (*num, extra, counts[*child].as_ref().unwrap())
+            });
+            counts[bag] = Some(
+                sum_terms(terms).ok_or_else(|| CountError::Overflow(self.name(bag).to_string()))?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Number of `inner` bags inside a single `outer` bag counted in `T`, or
+    /// of every bag when `inner` is `None`
+    pub fn count_inside<T: BagCount>(
+        &self,
+        outer: usize,
+        inner: Option<usize>,
+    ) -> Result<T, CountError> {
+        let mut counts: Vec<Option<T>> = vec![None; self.id_bound()];
+        // Children always come before their parents in the walk order
+        self.fill_counts(self.order(outer, Direction::Down)?, inner, &mut counts)?;
+        Ok(counts[outer].take().unwrap())
+    }
+
+    /// Every bag reachable from `id` in `direction`, including `id`, where
+    /// each bag comes after all of the bags it leads to
+    pub fn order(&self, id: usize, direction: Direction) -> Result<Vec<usize>, Cycle> {
+        let mut visits = vec![Visit::New; self.id_bound()];
+        let mut order = vec![];
+        self.walk(id, direction, &mut visits, &mut order)?;
+        Ok(order)
+    }
+
+    /// The first cycle in the rules, if any bag ends up inside itself
+    pub fn find_cycle(&self) -> Option<Cycle> {
+        let mut visits = vec![Visit::New; self.id_bound()];
+        let mut order = vec![];
+        self.ids().find_map(|id| {
+            self.walk(id, Direction::Down, &mut visits, &mut order)
+                .err()
+        })
+    }
+
+    /// Whether each bag can be reached from `id` in `direction`, including
+    /// `id` itself. Unlike the queries this never fails on cycles.
+    pub fn reachable(&self, id: usize, direction: Direction) -> Vec<bool> {
+        let mut seen = vec![false; self.id_bound()];
+        seen[id] = true;
+        let mut stack = vec![id];
+        while let Some(bag) = stack.pop() {
+            for (next, _) in self.edges(bag, direction) {
+                if !seen[*next] {
+                    seen[*next] = true;
+                    stack.push(*next);
+                }
+            }
+        }
+        seen
+    }
+
+    pub fn edges(&self, id: usize, direction: Direction) -> &[(usize, usize)] {
+        match direction {
+            Direction::Down => self.contains(id),
+            Direction::Up => self.contained_by(id),
+        }
+    }
+
+    /// Depth-first walk from `start` in `direction` without recursion, pushing
+    /// each bag to `order` once all of the bags it leads to have been pushed.
+    /// Bags already finished in `visits` are skipped, so one set of visits can
+    /// be shared across several walks.
+    fn walk(
+        &self,
+        start: usize,
+        direction: Direction,
+        visits: &mut [Visit],
+        order: &mut Vec<usize>,
+    ) -> Result<(), Cycle> {
+        if visits[start] == Visit::Done {
+            return Ok(());
+        }
+        visits[start] = Visit::Open;
+        // Bags on the current path along with the next edge to follow
+        let mut path = vec![(start, 0)];
+
+        while let Some((id, next)) = path.last_mut() {
+            let id = *id;
+            match self.edges(id, direction).get(*next) {
+                Some((to, _)) => {
+                    *next += 1;
+                    match visits[*to] {
+                        Visit::New => {
+                            visits[*to] = Visit::Open;
+                            path.push((*to, 0));
+                        }
+                        Visit::Open => {
+                            let from = path.iter().position(|(bag, _)| bag == to).unwrap();
+                            let mut ids: Vec<usize> =
+                                path[from..].iter().map(|(bag, _)| *bag).collect();
+                            ids.push(*to);
+                            // Walks up find the cycle
+                            // from the inside out
+                            if direction == Direction::Up {
+                                ids.reverse();
+                            }
+                            return Err(Cycle(
+                                ids.into_iter()
+                                    .map(|bag| self.name(bag).to_string())
+                                    .collect(),
+                            ));
+                        }
+                        Visit::Done => {}
+                    }
+                }
+                None => {
+                    visits[id] = Visit::Done;
+                    order.push(id);
+                    path.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes every rule back out, one per line in the order they were added
+impl fmt::Display for BagGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rule in self.rule_order.iter().filter_map(|id| self.rule(*id)) {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a walk follows the bags inside each bag or the bags holding it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Down,
+    Up,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    Open,
+    Done,
+}
+
+/// Colours that contain each other in turn, starting and ending with the same
+/// colour
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle(pub Vec<String>);
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bags contain themselves: {}", self.0.join(" -> "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CountError {
+    Cycle(Cycle),
+    /// The total inside a bag of this colour is too large to count
+    Overflow(String),
+}
+
+impl fmt::Display for CountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Cycle(cycle) => write!(f, "{}", cycle),
+            Self::Overflow(color) => write!(f, "Too many bags to count inside {}", color),
+        }
+    }
+}
+
+impl From<Cycle> for CountError {
+    fn from(cycle: Cycle) -> Self {
+        Self::Cycle(cycle)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use num_bigint::BigUint;
+
+    use super::super::query::{roots, within, QueryError};
+    use super::*;
+
+    #[test]
+    fn test_graph_adjacency() {
+        let graph = BagGraph::parse(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+faded blue bags contain no other bags.",
+        )
+        .unwrap();
+        let red = graph.id("light red").unwrap();
+        let yellow = graph.id("muted yellow").unwrap();
+        let gold = graph.id("shiny gold").unwrap();
+
+        assert_eq!(graph.len(), 5);
+        assert_eq!(
+            graph.contains(red),
+            &[(graph.id("bright white").unwrap(), 1), (yellow, 2)]
+        );
+        assert_eq!(
+            graph.contained_by(gold),
+            &[(graph.id("bright white").unwrap(), 1), (yellow, 2)]
+        );
+        assert_eq!(graph.name(gold), "shiny gold");
+        assert_eq!(graph.bags_contained("muted yellow"), Ok(11));
+        assert_eq!(graph.bags_contained("light red"), Ok(1 + 1 + 2 * 12));
+        assert!(graph.bags_containing("light red").unwrap().is_empty());
+        assert!(graph.bags_containing("unknown").unwrap().is_empty());
+        assert_eq!(graph.find_cycle(), None);
+    }
+
+    #[test]
+    fn test_exact_colors() {
+        let graph = BagGraph::parse(
+            "very shiny gold bags contain 2 gold bags, 1 shiny gold bag.
+
+shiny gold bags contain 3 gold bags.
+gold bags contain no other bags.",
+        )
+        .unwrap();
+        assert_eq!(graph.len(), 3);
+        assert_eq!(
+            graph.bags_containing("gold").unwrap(),
+            vec!["shiny gold", "very shiny gold"]
+        );
+        assert_eq!(
+            graph.bags_containing("shiny gold").unwrap(),
+            vec!["very shiny gold"]
+        );
+        assert_eq!(graph.bags_contained("very shiny gold"), Ok(2 + 1 + 3));
+
+        assert_eq!(
+            BagGraph::parse("gold bags contain no other bags.\n\nred bags hold 1 gold bag.")
+                .unwrap_err()
+                .line,
+            3
+        );
+        assert_eq!(
+            BagGraph::parse("gold bags contain no other bags.\ngold bags contain 1 red bag.")
+                .unwrap_err(),
+            ParseError {
+                line: 2,
+                message: String::from("Duplicate rule for gold"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_cycles() {
+        let graph = BagGraph::parse(
+            "light red bags contain 1 dark orange bag.
+dark orange bags contain 2 muted yellow bags, 1 faded blue bag.
+muted yellow bags contain 1 light red bag.
+faded blue bags contain no other bags.
+shiny gold bags contain 1 light red bag.",
+        )
+        .unwrap();
+        let cycle = Cycle(
+            ["light red", "dark orange", "muted yellow", "light red"]
+                .iter()
+                .map(|c| c.to_string())
+                .collect(),
+        );
+        assert_eq!(graph.find_cycle(), Some(cycle.clone()));
+        assert_eq!(
+            graph.bags_contained("shiny gold"),
+            Err(CountError::Cycle(cycle))
+        );
+        assert_eq!(
+            graph
+                .bags_containing("dark orange")
+                .unwrap_err()
+                .to_string(),
+            "Bags contain themselves: dark orange -> muted yellow -> light red -> dark orange"
+        );
+        assert_eq!(graph.bags_contained("faded blue"), Ok(0));
+        assert_eq!(graph.bags_containing("faded blue").unwrap_err().0.len(), 4);
+
+        let graph = BagGraph::parse("gold bags contain 1 gold bag.").unwrap();
+        assert_eq!(
+            graph.find_cycle(),
+            Some(Cycle(vec![String::from("gold"), String::from("gold")]))
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let rules: String = (0..8)
+            .map(|n| format!("bag{} bags contain 1000 bag{} bags.\n", n, n + 1))
+            .collect();
+        let graph = BagGraph::parse(&rules).unwrap();
+        assert_eq!(
+            graph.bags_contained("bag0"),
+            Err(CountError::Overflow(String::from("bag1")))
+        );
+        assert_eq!(graph.bags_contained("bag6"), Ok(1000 + 1000 * 1000));
+
+        let exact: BigUint = graph.count_inside(graph.id("bag0").unwrap(), None).unwrap();
+        assert_eq!(
+            exact,
+            (1..=8u32).map(|n| BigUint::from(1000u32).pow(n)).sum()
+        );
+    }
+
+    #[test]
+    fn test_editing() {
+        let rules = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain no other bags.
+";
+        let mut graph = BagGraph::parse(rules).unwrap();
+        assert_eq!(graph.to_string(), rules);
+        assert_eq!(graph.bags_contained("light red"), Ok(1 + 1 + 2 * 12));
+
+        graph.set_rule(&parse_rule("shiny gold bags contain 3 dark olive bags.").unwrap());
+        assert_eq!(graph.bags_contained("muted yellow"), Ok(2 * 4 + 9));
+        assert_eq!(graph.bags_contained("light red"), Ok(1 + 4 + 2 * 18));
+
+        assert!(graph.remove_rule("muted yellow"));
+        assert!(!graph.remove_rule("muted yellow"));
+        assert_eq!(graph.bags_contained("light red"), Ok(1 + 4 + 2));
+        assert_eq!(
+            graph.bags_containing("faded blue").unwrap(),
+            Vec::<String>::new()
+        );
+        assert!(graph.ids().all(|id| graph.name(id) != "faded blue"));
+        assert_eq!(
+            BagGraph::parse(&graph.to_string()).unwrap().to_string(),
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 3 dark olive bags.
+"
+        );
+
+        graph.set_rule(&parse_rule("pale cyan bags contain 1 shiny gold bag.").unwrap());
+        assert_eq!(roots(&graph), vec!["light red", "pale cyan"]);
+        assert!(graph.remove_rule("pale cyan"));
+        assert_eq!(roots(&graph), vec!["light red"]);
+        assert_eq!(graph.id("pale cyan"), None);
+        assert_eq!(graph.len(), 5);
+        assert!(matches!(
+            within(&graph, "pale cyan", 2),
+            Err(QueryError::UnknownColor(_))
+        ));
+
+        graph.set_rule(&parse_rule("dark olive bags contain 1 light red bag.").unwrap());
+        assert!(graph.bags_contained("light red").is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod count;
+pub mod export;
+pub mod graph;
+pub mod parse;
+pub mod query;
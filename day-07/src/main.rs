@@ -1,74 +1,140 @@
+use std::env;
 use std::fs;
+use std::str::FromStr;
 
-use lazy_static::lazy_static;
-use regex::Regex;
+use num_bigint::BigUint;
 
-fn bags_containing(bag_color: String, rules: &str) -> Vec<String> {
-    let bag_colors: Vec<String> = rules
-        .split('\n')
-        .filter(|s| s.contains(&format!(" {}", bag_color)))
-        .map(|s| s.split(' ').take(2).collect::<Vec<&str>>().join(" "))
-        .collect();
+use day_07::count::Arithmetic;
+use day_07::export::{export, Format};
+use day_07::graph::{BagGraph, Direction};
+use day_07::query::Query;
 
-    let parent_bag_colors: Vec<String> = bag_colors
-        .iter()
-        .flat_map(|c| bags_containing(c.to_string(), rules))
-        .collect();
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Counts in arbitrary precision instead of failing on overflow
+    let arithmetic = match args.iter().position(|arg| arg == "--exact") {
+        Some(idx) => {
+            args.remove(idx);
+            Arithmetic::Exact
+        }
+        None => Arithmetic::Checked,
+    };
+    // Reads the rules from `--input PATH` instead of the puzzle input
+    let path = match args.iter().position(|arg| arg == "--input") {
+        Some(idx) if idx + 1 < args.len() => {
+            args.remove(idx);
+            args.remove(idx)
+        }
+        Some(_) => {
+            eprintln!("Missing a value for --input");
+            return;
+        }
+        None => "./day-07/input.txt".to_string(),
+    };
+    let input = match fs::read_to_string(&path) {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("Can't read {}: {}", path, e);
+            return;
+        }
+    };
+    let graph = match BagGraph::parse(&input) {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
 
-    bag_colors
+    match args
         .iter()
-        .chain(parent_bag_colors.iter())
-        .map(|s| s.to_string())
-        .collect()
-}
-
-fn unique_bags_containing(bag_color: String, rules: &str) -> Vec<String> {
-    // We're only looking for bags that are contained, so adding a leading
-    // space eliminates rules that start with the bag color
-    // Then pull the first two words which are the color of the containing bag
-    let mut bags_containing_color = bags_containing(bag_color, rules);
-    bags_containing_color.sort_unstable();
-    bags_containing_color.dedup();
-    bags_containing_color
-}
-
-fn bags_contained(bag_color: String, rules: &str, initial: usize) -> usize {
-    lazy_static! {
-        static ref BAG_COLOR_RE: Regex =
-            Regex::new(r"(?P<num>\d+) (?P<color>[a-z]+ [a-z]+)").unwrap();
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        // Writes the rules back out in a consistent format
+        ["rules"] => {
+            print!("{}", graph);
+            return;
+        }
+        // Checks the rules for bags that end up inside themselves
+        ["validate"] => {
+            match graph.find_cycle() {
+                Some(cycle) => println!("{}", cycle),
+                None => println!("No cycles in {} colours", graph.len()),
+            }
+            return;
+        }
+        // Draws the rules, optionally only the bags inside or outside a
+        // colour, e.g. `export dot inside shiny gold`
+        ["export", format, rest @ ..] => {
+            let format = match Format::from_str(format) {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            let focus = match rest {
+                [] => None,
+                [side @ ("inside" | "outside"), color @ ..] => {
+                    let color = color.join(" ");
+                    let id = match graph.id(&color) {
+                        Some(id) => id,
+                        None => {
+                            eprintln!("Unknown colour {}", color);
+                            return;
+                        }
+                    };
+                    let direction = if *side == "inside" {
+                        Direction::Down
+                    } else {
+                        Direction::Up
+                    };
+                    Some((id, direction))
+                }
+                _ => {
+                    eprintln!("Expected inside or outside followed by a colour");
+                    return;
+                }
+            };
+            print!("{}", export(&graph, format, focus));
+            return;
+        }
+        // Answers a query such as `query paths light red to shiny gold`
+        ["query", query @ ..] => {
+            let query = match Query::from_str(&query.join(" ")) {
+                Ok(query) => query,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            match query.run(&graph, arithmetic) {
+                Ok(answer) => print!("{}", answer),
+                Err(e) => eprintln!("{}", e),
+            }
+            return;
+        }
+        _ => {}
     }
-    initial
-        + rules
-            .split('\n')
-            .filter(|s| s.contains(&format!("{} bags contain", bag_color)))
-            .map(|c| {
-                BAG_COLOR_RE
-                    .captures_iter(c)
-                    .map(|cap| {
-                        let num = cap
-                            .name("num")
-                            .map(|n| n.as_str().parse::<usize>().unwrap())
-                            .unwrap();
-                        let color = cap.name("color").unwrap().as_str();
-                        num * bags_contained(String::from(color), rules, 1)
-                    })
-                    .sum::<usize>()
-            })
-            .sum::<usize>()
-}
-
-fn main() {
-    let input = fs::read_to_string("./day-07/input.txt").unwrap();
 
-    println!(
-        "Part 1: {}",
-        unique_bags_containing(String::from("shiny gold"), &input).len()
-    );
+    match graph.bags_containing("shiny gold") {
+        Ok(colors) => println!("Part 1: {}", colors.len()),
+        Err(cycle) => eprintln!("{}", cycle),
+    }
 
-    println!(
-        "Part 2: {}",
-        bags_contained(String::from("shiny gold"), &input, 0)
-    );
+    let part_2 = match arithmetic {
+        Arithmetic::Checked => graph.bags_contained("shiny gold").map(BigUint::from),
+        // Like the checked count, a missing colour holds nothing
+        Arithmetic::Exact => graph
+            .id("shiny gold")
+            .map_or(Ok(BigUint::from(0u32)), |id| graph.count_inside(id, None)),
+    };
+    match part_2 {
+        Ok(count) => println!("Part 2: {}", count),
+        Err(e) => eprintln!("{}", e),
+    }
 }
 
 #[cfg(test)]
@@ -87,7 +153,11 @@ vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
 faded blue bags contain no other bags.
 dotted black bags contain no other bags.";
         assert_eq!(
-            unique_bags_containing(String::from("shiny gold"), rules).len(),
+            BagGraph::parse(rules)
+                .unwrap()
+                .bags_containing("shiny gold")
+                .unwrap()
+                .len(),
             4
         )
     }
@@ -101,6 +171,9 @@ dark yellow bags contain 2 dark green bags.
 dark green bags contain 2 dark blue bags.
 dark blue bags contain 2 dark violet bags.
 dark violet bags contain no other bags.";
-        assert_eq!(bags_contained(String::from("shiny gold"), rules, 0), 126);
+        assert_eq!(
+            BagGraph::parse(rules).unwrap().bags_contained("shiny gold"),
+            Ok(126)
+        );
     }
 }
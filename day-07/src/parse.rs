@@ -0,0 +1,212 @@
+use std::fmt;
+
+/// A single rule such as `bright white bags contain 1 shiny gold bag.`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub color: String,
+    /// Bags held directly, as `(count, color)`
+    pub contents: Vec<(usize, String)>,
+}
+
+/// Writes the rule back out the way it would appear in the input
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bags contain ", self.color)?;
+        if self.contents.is_empty() {
+            return write!(f, "no other bags.");
+        }
+        let contents: Vec<String> = self
+            .contents
+            .iter()
+            .map(|(num, color)| {
+                let noun = if *num == 1 { "bag" } else { "bags" };
+                format!("{} {} {}", num, color, noun)
+            })
+            .collect();
+        write!(f, "{}.", contents.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Comma,
+    Period,
+}
+
+fn tokenize(rule: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    for word in rule.split_whitespace() {
+        let mut rest = word;
+        while !rest.is_empty() {
+            match rest.find([',', '.']) {
+                Some(0) => {
+                    tokens.push(if rest.starts_with(',') {
+                        Token::Comma
+                    } else {
+                        Token::Period
+                    });
+                    rest = &rest[1..];
+                }
+                Some(idx) => {
+                    tokens.push(Token::Word(&rest[..idx]));
+                    rest = &rest[idx..];
+                }
+                None => {
+                    tokens.push(Token::Word(rest));
+                    rest = "";
+                }
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("Expected {:?}, found end of rule", expected)),
+        }
+    }
+
+    /// Reads colour words up to the `bag` or `bags` that ends them
+    fn color(&mut self) -> Result<(String, &'a str), String> {
+        let mut words = vec![];
+        loop {
+            match self.next() {
+                Some(Token::Word(noun @ ("bag" | "bags"))) if !words.is_empty() => {
+                    return Ok((words.join(" "), noun))
+                }
+                Some(Token::Word("bag" | "bags")) => return Err(String::from("Missing colour")),
+                Some(Token::Word(word)) => words.push(word),
+                Some(token) => return Err(format!("Unexpected {:?} in colour", token)),
+                None => return Err(String::from("Unexpected end of rule in colour")),
+            }
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, String> {
+        let (color, noun) = self.color()?;
+        if noun != "bags" {
+            return Err(format!("Expected \"{} bags\"", color));
+        }
+        self.expect(Token::Word("contain"))?;
+
+        let mut contents = vec![];
+        if self.peek() == Some(Token::Word("no")) {
+            for word in ["no", "other", "bags"].iter() {
+                self.expect(Token::Word(word))?;
+            }
+        } else {
+            loop {
+                let count = match self.next() {
+                    Some(Token::Word(num)) => num
+                        .parse::<usize>()
+                        .map_err(|_| format!("Expected a count, found {:?}", num))?,
+                    token => return Err(format!("Expected a count, found {:?}", token)),
+                };
+                let (child, noun) = self.color()?;
+                match (count, noun) {
+                    (0, _) => return Err(format!("Count for {} must be positive", child)),
+                    (1, "bags") => return Err(format!("Expected \"1 {} bag\"", child)),
+                    (1, _) | (_, "bags") => {}
+                    _ => return Err(format!("Expected \"{} {} bags\"", count, child)),
+                }
+                contents.push((count, child));
+                if self.peek() != Some(Token::Comma) {
+                    break;
+                }
+                self.next();
+            }
+        }
+
+        self.expect(Token::Period)?;
+        if let Some(token) = self.peek() {
+            return Err(format!("Unexpected {:?} after end of rule", token));
+        }
+        Ok(Rule { color, contents })
+    }
+}
+
+pub fn parse_rule(rule: &str) -> Result<Rule, String> {
+    Parser {
+        tokens: tokenize(rule),
+        pos: 0,
+    }
+    .rule()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            parse_rule("very shiny gold bags contain 1 gold bag, 12 dark olive green bags."),
+            Ok(Rule {
+                color: String::from("very shiny gold"),
+                contents: vec![
+                    (1, String::from("gold")),
+                    (12, String::from("dark olive green"))
+                ],
+            })
+        );
+        let rule = "very shiny gold bags contain 1 gold bag, 12 dark olive green bags.";
+        assert_eq!(parse_rule(rule).unwrap().to_string(), rule);
+        assert_eq!(
+            parse_rule("gold bags contain no other bags."),
+            Ok(Rule {
+                color: String::from("gold"),
+                contents: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_rule_errors() {
+        assert!(parse_rule("gold bags contain 2 red bag.").is_err());
+        assert!(parse_rule("gold bags contain 1 red bags.").is_err());
+        assert!(parse_rule("gold bags contain 0 red bags.").is_err());
+        assert!(parse_rule("gold bag contain 1 red bag.").is_err());
+        assert!(parse_rule("gold bags contain 1 red bag").is_err());
+        assert!(parse_rule("gold bags contain no bags.").is_err());
+        assert!(parse_rule("bags contain 1 red bag.").is_err());
+        assert!(parse_rule("gold bags contain 1 red bag. extra").is_err());
+        assert_eq!(
+            parse_rule("red bags hold 1 gold bag."),
+            Err(String::from(
+                "Expected Word(\"contain\"), found Word(\"hold\")"
+            ))
+        );
+    }
+}
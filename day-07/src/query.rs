@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+
+use super::count::{Arithmetic, BagCount};
+use super::graph::{BagGraph, CountError, Cycle, Direction};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryError {
+    UnknownColor(String),
+    Cycle(Cycle),
+    Count(CountError),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownColor(color) => write!(f, "Unknown colour {}", color),
+            Self::Cycle(cycle) => write!(f, "{}", cycle),
+            Self::Count(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<Cycle> for QueryError {
+    fn from(cycle: Cycle) -> Self {
+        Self::Cycle(cycle)
+    }
+}
+
+impl From<CountError> for QueryError {
+    fn from(e: CountError) -> Self {
+        match e {
+            CountError::Cycle(cycle) => Self::Cycle(cycle),
+            e => Self::Count(e),
+        }
+    }
+}
+
+/// Fewest and most levels of nesting between two bags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Depth {
+    pub min: usize,
+    pub max: usize,
+}
+
+fn id(graph: &BagGraph, color: &str) -> Result<usize, QueryError> {
+    graph
+        .id(color)
+        .ok_or_else(|| QueryError::UnknownColor(color.to_string()))
+}
+
+fn names(graph: &BagGraph, ids: impl Iterator<Item = usize>) -> Vec<String> {
+    let mut names: Vec<String> = ids.map(|id| graph.name(id).to_string()).collect();
+    names.sort_unstable();
+    names
+}
+
+/// Every chain of bags from `from` down to `to`, both included
+pub fn paths(graph: &BagGraph, from: &str, to: &str) -> Result<Vec<Vec<String>>, QueryError> {
+    let (from, to) = (id(graph, from)?, id(graph, to)?);
+    let order = graph.order(from, Direction::Down)?;
+
+    // Only bags that lead to `to` are worth following
+    let mut leads_to = vec![false; graph.id_bound()];
+    for bag in order {
+        leads_to[bag] = bag == to || graph.contains(bag).iter().any(|(c, _)| leads_to[*c]);
+    }
+
+    let mut paths = vec![];
+    let mut path = vec![(from, 0)];
+    while let Some((bag, next)) = path.last_mut() {
+        let bag = *bag;
+        if bag == to {
+            paths.push(
+                path.iter()
+                    .map(|(b, _)| graph.name(*b).to_string())
+                    .collect(),
+            );
+            path.pop();
+            continue;
+        }
+        match graph.contains(bag).get(*next) {
+            Some((child, _)) => {
+                *next += 1;
+                if leads_to[*child] {
+                    path.push((*child, 0));
+                }
+            }
+            None => {
+                path.pop();
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Fewest and most levels between `from` and a `to` bag inside it, or `None`
+/// if `to` is never inside `from`
+pub fn depth(graph: &BagGraph, from: &str, to: &str) -> Result<Option<Depth>, QueryError> {
+    let (from, to) = (id(graph, from)?, id(graph, to)?);
+    let mut depths: Vec<Option<Depth>> = vec![None; graph.id_bound()];
+    for bag in graph.order(from, Direction::Down)? {
+        depths[bag] = if bag == to {
+            Some(Depth { min: 0, max: 0 })
+        } else {
+            graph
+                .contains(bag)
+                .iter()
+                .filter_map(|(child, _)| depths[*child])
+                .fold(None, |acc: Option<Depth>, d| {
+                    Some(Depth {
+                        min: acc.map_or(d.min + 1, |a| a.min.min(d.min + 1)),
+                        max: acc.map_or(d.max + 1, |a| a.max.max(d.max + 1)),
+                    })
+                })
+        };
+    }
+    Ok(depths[from])
+}
+
+/// Number of `inner` bags somewhere inside a single `outer` bag, counted in
+/// `T`
+pub fn count<T: BagCount>(graph: &BagGraph, outer: &str, inner: &str) -> Result<T, QueryError> {
+    let (outer, inner) = (id(graph, outer)?, id(graph, inner)?);
+    Ok(graph.count_inside(outer, Some(inner))?)
+}
+
+/// Colours at most `levels` levels inside `color`, with the fewest levels
+/// each is found at, sorted by level and then colour
+pub fn within(
+    graph: &BagGraph,
+    color: &str,
+    levels: usize,
+) -> Result<Vec<(String, usize)>, QueryError> {
+    let start = id(graph, color)?;
+    let mut level = vec![None; graph.id_bound()];
+    let mut queue = VecDeque::new();
+    queue.push_back((start, 0));
+
+    // Breadth first, so each bag is first reached at its shallowest level
+    while let Some((bag, depth)) = queue.pop_front() {
+        if depth == levels {
+            continue;
+        }
+        for (child, _) in graph.contains(bag) {
+            if level[*child].is_none() && *child != start {
+                level[*child] = Some(depth + 1);
+                queue.push_back((*child, depth + 1));
+            }
+        }
+    }
+
+    let mut colors: Vec<(String, usize)> = (0..graph.id_bound())
+        .filter_map(|bag| level[bag].map(|l| (graph.name(bag).to_string(), l)))
+        .collect();
+    colors.sort_unstable_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+    Ok(colors)
+}
+
+/// Colours that hold no other bags, sorted
+pub fn leaves(graph: &BagGraph) -> Vec<String> {
+    names(
+        graph,
+        graph.ids().filter(|id| graph.contains(*id).is_empty()),
+    )
+}
+
+/// Colours that are never inside another bag, sorted
+pub fn roots(graph: &BagGraph) -> Vec<String> {
+    names(
+        graph,
+        graph.ids().filter(|id| graph.contained_by(*id).is_empty()),
+    )
+}
+
+/// A question about the rules, written as one of
+///
+/// - `paths <colour> to <colour>`
+/// - `depth <colour> to <colour>`
+/// - `count <inner colour> in <outer colour>`
+/// - `within <levels> <colour>`
+/// - `leaves` or `roots`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    Paths(String, String),
+    Depth(String, String),
+    Count { inner: String, outer: String },
+    Within(usize, String),
+    Leaves,
+    Roots,
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pair = |rest: &str, sep: &str| {
+            rest.split_once(sep)
+                .map(|(a, b)| (a.trim().to_string(), b.trim().to_string()))
+                .ok_or_else(|| format!("Expected \"<colour>{}<colour>\"", sep))
+        };
+        let (name, rest) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        match name {
+            "paths" => pair(rest, " to ").map(|(a, b)| Self::Paths(a, b)),
+            "depth" => pair(rest, " to ").map(|(a, b)| Self::Depth(a, b)),
+            "count" => pair(rest, " in ").map(|(inner, outer)| Self::Count { inner, outer }),
+            "within" => {
+                let (levels, color) = rest
+                    .split_once(' ')
+                    .ok_or_else(|| String::from("Expected \"within <levels> <colour>\""))?;
+                let levels = levels
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid levels {}", levels))?;
+                Ok(Self::Within(levels, color.trim().to_string()))
+            }
+            "leaves" => Ok(Self::Leaves),
+            "roots" => Ok(Self::Roots),
+            _ => Err(format!("Unknown query {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Answer {
+    Paths(Vec<Vec<String>>),
+    Depth(Option<Depth>),
+    Count(BigUint),
+    Levels(Vec<(String, usize)>),
+    Colors(Vec<String>),
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Paths(paths) => {
+                for path in paths {
+                    writeln!(f, "{}", path.join(" -> "))?;
+                }
+                Ok(())
+            }
+            Self::Depth(Some(depth)) => writeln!(f, "min {}, max {}", depth.min, depth.max),
+            Self::Depth(None) => writeln!(f, "Never inside"),
+            Self::Count(count) => writeln!(f, "{}", count),
+            Self::Levels(colors) => {
+                for (color, level) in colors {
+                    writeln!(f, "{} {}", level, color)?;
+                }
+                Ok(())
+            }
+            Self::Colors(colors) => {
+                for color in colors {
+                    writeln!(f, "{}", color)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Query {
+    pub fn run(&self, graph: &BagGraph, arithmetic: Arithmetic) -> Result<Answer, QueryError> {
+        Ok(match self {
+            Self::Paths(from, to) => Answer::Paths(paths(graph, from, to)?),
+            Self::Depth(from, to) => Answer::Depth(depth(graph, from, to)?),
+            Self::Count { inner, outer } => Answer::Count(match arithmetic {
+                Arithmetic::Checked => BigUint::from(count::<usize>(graph, outer, inner)?),
+                Arithmetic::Exact => count(graph, outer, inner)?,
+            }),
+            Self::Within(levels, color) => Answer::Levels(within(graph, color, *levels)?),
+            Self::Leaves => Answer::Colors(leaves(graph)),
+            Self::Roots => Answer::Colors(roots(graph)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> BagGraph {
+        BagGraph::parse(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_queries() {
+        let graph = sample();
+        assert_eq!(
+            paths(&graph, "light red", "shiny gold").unwrap(),
+            vec![
+                vec!["light red", "bright white", "shiny gold"],
+                vec!["light red", "muted yellow", "shiny gold"],
+            ]
+        );
+        assert_eq!(paths(&graph, "light red", "faded blue").unwrap().len(), 5);
+        assert_eq!(
+            depth(&graph, "light red", "faded blue"),
+            Ok(Some(Depth { min: 2, max: 4 }))
+        );
+        assert_eq!(depth(&graph, "faded blue", "light red"), Ok(None));
+        assert_eq!(
+            count::<usize>(&graph, "shiny gold", "faded blue"),
+            Ok(3 + 2 * 5)
+        );
+        assert_eq!(
+            count::<usize>(&graph, "light red", "shiny gold"),
+            Ok(1 + 2 * 2)
+        );
+        assert_eq!(
+            within(&graph, "shiny gold", 1).unwrap(),
+            vec![
+                (String::from("dark olive"), 1),
+                (String::from("vibrant plum"), 1)
+            ]
+        );
+        assert_eq!(within(&graph, "muted yellow", 2).unwrap().len(), 4);
+        assert_eq!(leaves(&graph), vec!["dotted black", "faded blue"]);
+        assert_eq!(roots(&graph), vec!["dark orange", "light red"]);
+        assert_eq!(
+            count::<usize>(&graph, "light red", "unknown"),
+            Err(QueryError::UnknownColor(String::from("unknown")))
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let graph = sample();
+        assert_eq!(
+            Query::from_str("count faded blue in shiny gold"),
+            Ok(Query::Count {
+                inner: String::from("faded blue"),
+                outer: String::from("shiny gold"),
+            })
+        );
+        assert_eq!(
+            Query::from_str("within 1 shiny gold")
+                .unwrap()
+                .run(&graph, Arithmetic::Checked)
+                .unwrap()
+                .to_string(),
+            "1 dark olive\n1 vibrant plum\n"
+        );
+        assert_eq!(
+            Query::from_str("depth light red to shiny gold")
+                .unwrap()
+                .run(&graph, Arithmetic::Checked)
+                .unwrap()
+                .to_string(),
+            "min 2, max 2\n"
+        );
+        assert!(Query::from_str("paths light red").is_err());
+        assert!(Query::from_str("within many shiny gold").is_err());
+    }
+}
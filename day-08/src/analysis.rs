@@ -0,0 +1,231 @@
+use super::instruction::Instruction;
+use super::machine::{Bounds, Halt};
+
+/// Where running `instruction` at `address` goes next: the next address, or
+/// `len` for termination, or `None` when it errors
+fn next_address(
+    instruction: Instruction,
+    address: usize,
+    len: usize,
+    bounds: Bounds,
+) -> Option<usize> {
+    match bounds.check(instruction.next(address), len) {
+        Ok(next) => Some(next),
+        Err(Halt::Terminated) => Some(len),
+        Err(_) => None,
+    }
+}
+
+/// Control-flow graph of a program. Every instruction has at most one
+/// successor, and address `len` stands for termination.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    /// Successor of each address, or `None` when running it errors
+    next: Vec<Option<usize>>,
+}
+
+impl Cfg {
+    pub fn new(program: &[Instruction], bounds: Bounds) -> Self {
+        Cfg {
+            next: (0..program.len())
+                .map(|address| next_address(program[address], address, program.len(), bounds))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.next.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next.is_empty()
+    }
+
+    pub fn next(&self, address: usize) -> Option<usize> {
+        self.next[address]
+    }
+
+    /// Addresses leading to each address, including the end
+    pub fn sources(&self) -> Vec<Vec<usize>> {
+        let mut sources = vec![vec![]; self.len() + 1];
+        for (address, next) in self.next.iter().enumerate() {
+            if let Some(next) = next {
+                sources[*next].push(address);
+            }
+        }
+        sources
+    }
+
+    /// Addresses run from the entry in order, until one repeats or the
+    /// program leaves
+    pub fn path(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.len()];
+        let mut path = vec![];
+        let mut address = Some(0).filter(|_| !self.is_empty());
+        while let Some(current) = address.filter(|a| *a < self.len() && !seen[*a]) {
+            seen[current] = true;
+            path.push(current);
+            address = self.next[current];
+        }
+        path
+    }
+
+    /// Whether running from each address terminates
+    pub fn terminating(&self) -> Vec<bool> {
+        // Walking the reversed edges back from the end finds everything that
+        // leads there
+        let sources = self.sources();
+        let mut ends = vec![false; self.len() + 1];
+        ends[self.len()] = true;
+        let mut stack = vec![self.len()];
+        while let Some(address) = stack.pop() {
+            for source in sources[address].iter() {
+                if !ends[*source] {
+                    ends[*source] = true;
+                    stack.push(*source);
+                }
+            }
+        }
+        ends.truncate(self.len());
+        ends
+    }
+
+    /// Single `jmp`/`nop` swaps in `program`, the program this graph was built
+    /// from, after which the entry terminates, in the order the entry
+    /// reaches them
+    pub fn flips(&self, program: &[Instruction], bounds: Bounds) -> Vec<(usize, Instruction)> {
+        let len = self.len();
+        let path = self.path();
+        let mut terminating = self.terminating();
+        terminating.push(true);
+
+        // A swap on the path works when the new successor terminates without
+        // running the swapped address again. From a terminating address that
+        // is true when its route to the end joins the path after the swap, so
+        // find the first path position along each route.
+        let mut position = vec![usize::MAX; len + 1];
+        path.iter()
+            .enumerate()
+            .filter(|(_, a)| terminating[**a])
+            .for_each(|(idx, a)| position[*a] = idx);
+        let sources = self.sources();
+        let mut stack = vec![len];
+        while let Some(address) = stack.pop() {
+            for source in sources[address].iter() {
+                if position[*source] == usize::MAX {
+                    position[*source] = position[address];
+                }
+                stack.push(*source);
+            }
+        }
+
+        path.iter()
+            .enumerate()
+            .filter_map(|(idx, address)| {
+                let flipped = program[*address].flipped()?;
+                let next = next_address(flipped, *address, len, bounds)?;
+                (terminating[next] && position[next] > idx).then_some((*address, flipped))
+            })
+            .collect()
+    }
+
+    /// Strongly connected components that can't be left, each listed from its
+    /// lowest address in the order it runs. With one successor per address
+    /// these are exactly the cycles.
+    pub fn loops(&self) -> Vec<Vec<usize>> {
+        // 0 for unseen, 1 for on the current walk and 2 for done
+        let mut state = vec![0u8; self.len()];
+        let mut loops = vec![];
+        for start in 0..self.len() {
+            let mut walk = vec![];
+            let mut address = Some(start);
+            while let Some(current) = address.filter(|a| *a < self.len() && state[*a] == 0) {
+                state[current] = 1;
+                walk.push(current);
+                address = self.next[current];
+            }
+            if let Some(repeat) = address.filter(|a| *a < self.len() && state[*a] == 1) {
+                let begin = walk.iter().position(|a| *a == repeat).unwrap();
+                let mut cycle = walk.split_off(begin);
+                let lowest = cycle.iter().enumerate().min_by_key(|(_, a)| **a).unwrap().0;
+                cycle.rotate_left(lowest);
+                loops.push(cycle);
+            }
+            walk.iter().for_each(|a| state[*a] = 2);
+            if let Some(cycle) = loops.last() {
+                cycle.iter().for_each(|a| state[*a] = 2);
+            }
+        }
+        loops
+    }
+}
+
+/// What can be learned about a program without running it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// Addresses the entry never reaches
+    pub unreachable: Vec<usize>,
+    /// Cycles that never exit
+    pub loops: Vec<Vec<usize>>,
+    /// Whether running from each address terminates
+    pub terminating: Vec<bool>,
+    /// Single `jmp`/`nop` swaps after which the entry terminates, in the
+    /// order the entry reaches them
+    pub flips: Vec<(usize, Instruction)>,
+}
+
+pub fn analyze(program: &[Instruction], bounds: Bounds) -> Analysis {
+    let cfg = Cfg::new(program, bounds);
+    let len = cfg.len();
+    let path = cfg.path();
+    let terminating = cfg.terminating();
+
+    let mut reachable = vec![false; len];
+    path.iter().for_each(|a| reachable[*a] = true);
+
+    let flips = cfg.flips(program, bounds);
+
+    Analysis {
+        unreachable: (0..len).filter(|a| !reachable[*a]).collect(),
+        loops: cfg.loops(),
+        terminating,
+        flips,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instruction::{parse_instructions, SAMPLE};
+    use super::*;
+
+    #[test]
+    fn test_analyze() {
+        let program = parse_instructions(SAMPLE).unwrap();
+        let analysis = analyze(&program, Bounds::Strict);
+        assert_eq!(analysis.unreachable, vec![5, 8]);
+        assert_eq!(analysis.loops, vec![vec![1, 2, 6, 7, 3, 4]]);
+        assert_eq!(
+            analysis.terminating,
+            vec![false, false, false, false, false, false, false, false, true]
+        );
+        assert_eq!(analysis.flips, vec![(7, Instruction::Nop(-4))]);
+    }
+
+    #[test]
+    fn test_flips_through_path() {
+        // Swapping the last nop jumps back to the start, which then runs it
+        // again, so only swapping the first jump works
+        let program = parse_instructions("jmp +2\nacc +1\nnop -2").unwrap();
+        let analysis = analyze(&program, Bounds::Strict);
+        assert_eq!(analysis.unreachable, vec![1]);
+        assert!(analysis.loops.is_empty());
+        assert_eq!(analysis.flips, vec![(0, Instruction::Nop(2))]);
+
+        let cfg = Cfg::new(
+            &parse_instructions("jmp +0\nacc +1\njmp -3").unwrap(),
+            Bounds::Strict,
+        );
+        assert_eq!(cfg.next(2), None);
+        assert_eq!(cfg.loops(), vec![vec![0]]);
+    }
+}
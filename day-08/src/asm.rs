@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::instruction::{parse_arg, Instruction};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Assembles source with one instruction per line into a program. Lines may
+/// start with a `label:` and anything after `;` is a comment. Jumps and nops
+/// take either a signed offset or a label, which can also be defined after
+/// the last instruction to jump to the end.
+///
+/// ```text
+/// loop: acc +1 ; count up
+///       jmp loop
+/// ```
+pub fn assemble(source: &str) -> Result<Vec<Instruction>, AsmError> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    // Opcode and argument of each instruction, along with its line
+    let mut pending: Vec<(usize, &str, &str)> = vec![];
+
+    for (idx, line) in source.lines().enumerate() {
+        let error = |message| AsmError {
+            line: idx + 1,
+            message,
+        };
+        let mut code = line.split(';').next().unwrap().trim();
+        if let Some((label, rest)) = code.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(error(format!("Invalid label {:?}", label)));
+            }
+            if labels.insert(label, pending.len()).is_some() {
+                return Err(error(format!("Duplicate label {}", label)));
+            }
+            code = rest.trim();
+        }
+        match code.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [] => {}
+            [opcode, arg] => pending.push((idx + 1, opcode, arg)),
+            _ => {
+                return Err(error(format!(
+                    "Expected an opcode and argument, found {:?}",
+                    code
+                )))
+            }
+        }
+    }
+
+    pending
+        .iter()
+        .enumerate()
+        .map(|(address, (line, opcode, arg))| {
+            let error = |message| AsmError {
+                line: *line,
+                message,
+            };
+            let value = match labels.get(arg) {
+                Some(_) if *opcode == "acc" => {
+                    return Err(error(format!("acc can't take label {}", arg)))
+                }
+                Some(target) => *target as isize - address as isize,
+                None if is_label(arg) => return Err(error(format!("Undefined label {}", arg))),
+                None => parse_arg(arg).map_err(error)?,
+            };
+            Instruction::new(opcode, value)
+                .ok_or_else(|| error(format!("Unknown opcode {}", opcode)))
+        })
+        .collect()
+}
+
+/// Writes a program out as source for `assemble`, with a label at every
+/// address a jump lands on, including just past the end
+pub fn disassemble(program: &[Instruction]) -> String {
+    let len = program.len() as i128;
+    let target = |address: usize, instruction: &Instruction| match instruction {
+        Instruction::Jump(_) => Some(instruction.next(address)).filter(|t| (0..=len).contains(t)),
+        _ => None,
+    };
+
+    let mut targets: Vec<i128> = program
+        .iter()
+        .enumerate()
+        .filter_map(|(address, instruction)| target(address, instruction))
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let label = |address: i128| format!("l{}", targets.binary_search(&address).unwrap());
+
+    let mut source = String::new();
+    for address in 0..=program.len() {
+        if targets.binary_search(&(address as i128)).is_ok() {
+            source += &format!("{}:\n", label(address as i128));
+        }
+        let instruction = match program.get(address) {
+            Some(instruction) => instruction,
+            None => break,
+        };
+        source += &match target(address, instruction) {
+            Some(t) => format!("    {} {}\n", instruction.opcode(), label(t)),
+            None => format!("    {}\n", instruction),
+        };
+    }
+    source
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instruction::parse_instructions;
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(
+            "; adds one three times
+        nop +0
+loop:   acc +1 ; count up
+        jmp skip
+        acc -99
+skip:   jmp loop
+
+end:",
+        )
+        .unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Nop(0),
+                Instruction::Acc(1),
+                Instruction::Jump(2),
+                Instruction::Acc(-99),
+                Instruction::Jump(-3),
+            ]
+        );
+        assert_eq!(
+            assemble("acc +1\njmp done"),
+            Err(AsmError {
+                line: 2,
+                message: String::from("Undefined label done"),
+            })
+        );
+        assert!(assemble("a: nop +0\na: nop +0").is_err());
+        assert!(assemble("a: acc a").is_err());
+        assert!(assemble("mul +2").is_err());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let program = parse_instructions(
+            "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+jmp +2
+jmp -20",
+        )
+        .unwrap();
+        let source = disassemble(&program);
+        assert_eq!(
+            source,
+            "    nop +0
+l0:
+    acc +1
+    jmp l2
+l1:
+    acc +3
+    jmp l0
+    acc -99
+l2:
+    acc +1
+    jmp l1
+    jmp l3
+    jmp -20
+l3:
+"
+        );
+        assert_eq!(assemble(&source).unwrap(), program);
+    }
+}
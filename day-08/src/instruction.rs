@@ -0,0 +1,131 @@
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Acc(isize),
+    Jump(isize),
+    Nop(isize),
+}
+
+impl FromStr for Instruction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [opcode, arg] => {
+                let arg = parse_arg(arg)?;
+                Self::new(opcode, arg).ok_or_else(|| format!("Unknown opcode {}", opcode))
+            }
+            _ => Err(format!("Expected an opcode and argument, found {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:+}", self.opcode(), self.arg())
+    }
+}
+
+/// Parses a signed argument such as `+4` or `-3`
+pub fn parse_arg(arg: &str) -> Result<isize, String> {
+    arg.trim_start_matches('+')
+        .parse()
+        .map_err(|_| format!("Invalid argument {}", arg))
+}
+
+impl Instruction {
+    pub fn new(opcode: &str, arg: isize) -> Option<Self> {
+        match opcode {
+            "acc" => Some(Self::Acc(arg)),
+            "jmp" => Some(Self::Jump(arg)),
+            "nop" => Some(Self::Nop(arg)),
+            _ => None,
+        }
+    }
+
+    pub fn arg(&self) -> isize {
+        match self {
+            Self::Acc(v) | Self::Jump(v) | Self::Nop(v) => *v,
+        }
+    }
+
+    pub fn opcode(&self) -> &'static str {
+        match self {
+            Self::Acc(_) => "acc",
+            Self::Jump(_) => "jmp",
+            Self::Nop(_) => "nop",
+        }
+    }
+
+    /// Address of the instruction that runs after this one at `address`,
+    /// which may be outside the program. It is wider than `isize` so that
+    /// even the furthest jump gives its real target.
+    pub fn next(&self, address: usize) -> i128 {
+        match self {
+            Self::Jump(v) => address as i128 + *v as i128,
+            _ => address as i128 + 1,
+        }
+    }
+
+    /// The instruction swapped between `jmp` and `nop`, if it is either
+    pub fn flipped(&self) -> Option<Self> {
+        match self {
+            Self::Jump(v) => Some(Self::Nop(*v)),
+            Self::Nop(v) => Some(Self::Jump(*v)),
+            Self::Acc(_) => None,
+        }
+    }
+}
+
+/// The example program from the puzzle, which loops forever unless the
+/// `jmp -4` is swapped for a `nop`
+pub const SAMPLE: &str = "nop +0
+acc +1
+jmp +4
+acc +3
+jmp -3
+acc -99
+acc +1
+jmp -4
+acc +6";
+
+/// Parses one instruction per line, skipping blank lines and failing with the
+/// line number of the first invalid instruction
+pub fn parse_instructions(instructions: &str) -> Result<Vec<Instruction>, String> {
+    instructions
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            Instruction::from_str(line).map_err(|e| format!("Line {}: {}", idx + 1, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_instructions() {
+        let program = parse_instructions("nop +0\r\nacc -12\n\njmp 3\n").unwrap();
+        assert_eq!(
+            program,
+            vec![
+                Instruction::Nop(0),
+                Instruction::Acc(-12),
+                Instruction::Jump(3)
+            ]
+        );
+        assert_eq!(program[2].to_string(), "jmp +3");
+        assert_eq!(
+            parse_instructions("acc +1\nmul +2"),
+            Err(String::from("Line 2: Unknown opcode mul"))
+        );
+        assert!(parse_instructions("acc").is_err());
+        assert!(parse_instructions("acc +1 +2").is_err());
+        assert!(parse_instructions("acc one").is_err());
+    }
+}
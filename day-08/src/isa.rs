@@ -0,0 +1,443 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::instruction::{parse_arg, Instruction};
+
+/// Something an instruction couldn't do while running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    BadRegister(usize),
+    StackUnderflow,
+    NoInput,
+    Overflow,
+}
+
+/// State that opcodes act on. Register 0 is the accumulator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cpu {
+    pub pc: i128,
+    pub registers: Vec<isize>,
+    pub stack: Vec<isize>,
+    pub input: VecDeque<isize>,
+    pub output: Vec<isize>,
+}
+
+impl Cpu {
+    pub fn new(registers: usize) -> Self {
+        Cpu {
+            pc: 0,
+            registers: vec![0; registers.max(1)],
+            stack: vec![],
+            input: VecDeque::new(),
+            output: vec![],
+        }
+    }
+
+    pub fn value(&self, operand: Operand) -> Result<isize, Fault> {
+        match operand {
+            Operand::Imm(v) => Ok(v),
+            Operand::Reg(r) => self.registers.get(r).copied().ok_or(Fault::BadRegister(r)),
+        }
+    }
+
+    pub fn register(&mut self, operand: Operand) -> Result<&mut isize, Fault> {
+        match operand {
+            Operand::Reg(r) => self.registers.get_mut(r).ok_or(Fault::BadRegister(r)),
+            Operand::Imm(_) => Err(Fault::BadRegister(usize::MAX)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Imm(isize),
+    Reg(usize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Imm(v) => write!(f, "{:+}", v),
+            Self::Reg(r) => write!(f, "r{}", r),
+        }
+    }
+}
+
+/// What an opcode accepts in each operand position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// A register to write to, written `r0`, `r1` and so on
+    Register,
+    /// A register or a signed number
+    Value,
+    /// A signed number, such as a jump offset
+    Immediate,
+}
+
+/// An entry in an opcode table. `exec` is responsible for moving the program
+/// counter on.
+#[derive(Debug, Clone, Copy)]
+pub struct Opcode {
+    pub name: &'static str,
+    pub operands: &'static [Kind],
+    pub exec: fn(&mut Cpu, &[Operand]) -> Result<(), Fault>,
+}
+
+/// An instruction of any dialect
+#[derive(Debug, Clone)]
+pub struct Op {
+    pub opcode: Opcode,
+    pub operands: Vec<Operand>,
+}
+
+impl PartialEq for Op {
+    fn eq(&self, other: &Self) -> bool {
+        self.opcode.name == other.opcode.name && self.operands == other.operands
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode.name)?;
+        for operand in self.operands.iter() {
+            write!(f, " {}", operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// Moves the program counter by `offset`. The counter is wider than any
+/// offset, so even the furthest jump lands on its real target, which the
+/// machine then reports as out of bounds.
+fn jump(cpu: &mut Cpu, offset: isize) {
+    cpu.pc += offset as i128;
+}
+
+fn next(cpu: &mut Cpu) {
+    jump(cpu, 1);
+}
+
+pub const ACC: Opcode = Opcode {
+    name: "acc",
+    operands: &[Kind::Value],
+    exec: |cpu, ops| {
+        let v = cpu.value(ops[0])?;
+        cpu.registers[0] = cpu.registers[0].checked_add(v).ok_or(Fault::Overflow)?;
+        next(cpu);
+        Ok(())
+    },
+};
+
+pub const JMP: Opcode = Opcode {
+    name: "jmp",
+    operands: &[Kind::Immediate],
+    exec: |cpu, ops| {
+        jump(cpu, cpu.value(ops[0])?);
+        Ok(())
+    },
+};
+
+pub const NOP: Opcode = Opcode {
+    name: "nop",
+    operands: &[Kind::Immediate],
+    exec: |cpu, _| {
+        next(cpu);
+        Ok(())
+    },
+};
+
+pub const SET: Opcode = Opcode {
+    name: "set",
+    operands: &[Kind::Register, Kind::Value],
+    exec: |cpu, ops| {
+        let v = cpu.value(ops[1])?;
+        *cpu.register(ops[0])? = v;
+        next(cpu);
+        Ok(())
+    },
+};
+
+pub const ADD: Opcode = Opcode {
+    name: "add",
+    operands: &[Kind::Register, Kind::Value],
+    exec: |cpu, ops| {
+        let v = cpu.value(ops[1])?;
+        let r = cpu.register(ops[0])?;
+        *r = r.checked_add(v).ok_or(Fault::Overflow)?;
+        next(cpu);
+        Ok(())
+    },
+};
+
+pub const MUL: Opcode = Opcode {
+    name: "mul",
+    operands: &[Kind::Register, Kind::Value],
+    exec: |cpu, ops| {
+        let v = cpu.value(ops[1])?;
+        let r = cpu.register(ops[0])?;
+        *r = r.checked_mul(v).ok_or(Fault::Overflow)?;
+        next(cpu);
+        Ok(())
+    },
+};
+
+/// Jumps by the second operand when the first is zero
+pub const JZ: Opcode = Opcode {
+    name: "jz",
+    operands: &[Kind::Value, Kind::Immediate],
+    exec: |cpu, ops| {
+        match cpu.value(ops[0])? {
+            0 => jump(cpu, cpu.value(ops[1])?),
+            _ => next(cpu),
+        }
+        Ok(())
+    },
+};
+
+/// Jumps by the second operand when the first isn't zero
+pub const JNZ: Opcode = Opcode {
+    name: "jnz",
+    operands: &[Kind::Value, Kind::Immediate],
+    exec: |cpu, ops| {
+        match cpu.value(ops[0])? {
+            0 => next(cpu),
+            _ => jump(cpu, cpu.value(ops[1])?),
+        }
+        Ok(())
+    },
+};
+
+/// Pushes the address after the call and jumps by the offset
+pub const CALL: Opcode = Opcode {
+    name: "call",
+    operands: &[Kind::Immediate],
+    exec: |cpu, ops| {
+        // Only instructions inside the program run, so the address after
+        // one always fits
+        cpu.stack.push(cpu.pc as isize + 1);
+        jump(cpu, cpu.value(ops[0])?);
+        Ok(())
+    },
+};
+
+pub const RET: Opcode = Opcode {
+    name: "ret",
+    operands: &[],
+    exec: |cpu, _| {
+        cpu.pc = cpu.stack.pop().ok_or(Fault::StackUnderflow)? as i128;
+        Ok(())
+    },
+};
+
+pub const IN: Opcode = Opcode {
+    name: "in",
+    operands: &[Kind::Register],
+    exec: |cpu, ops| {
+        let v = cpu.input.pop_front().ok_or(Fault::NoInput)?;
+        *cpu.register(ops[0])? = v;
+        next(cpu);
+        Ok(())
+    },
+};
+
+pub const OUT: Opcode = Opcode {
+    name: "out",
+    operands: &[Kind::Value],
+    exec: |cpu, ops| {
+        let v = cpu.value(ops[0])?;
+        cpu.output.push(v);
+        next(cpu);
+        Ok(())
+    },
+};
+
+impl From<Instruction> for Op {
+    fn from(instruction: Instruction) -> Self {
+        let opcode = match instruction {
+            Instruction::Acc(_) => ACC,
+            Instruction::Jump(_) => JMP,
+            Instruction::Nop(_) => NOP,
+        };
+        Op {
+            opcode,
+            operands: vec![Operand::Imm(instruction.arg())],
+        }
+    }
+}
+
+/// An opcode table along with the number of registers programs can use
+#[derive(Debug, Clone)]
+pub struct Dialect {
+    opcodes: HashMap<&'static str, Opcode>,
+    registers: usize,
+}
+
+impl Default for Dialect {
+    /// The console's `acc`, `jmp` and `nop`, with only the accumulator
+    fn default() -> Self {
+        Dialect {
+            opcodes: HashMap::new(),
+            registers: 1,
+        }
+        .with_opcode(ACC)
+        .with_opcode(JMP)
+        .with_opcode(NOP)
+    }
+}
+
+impl Dialect {
+    /// The default dialect with eight registers, arithmetic, conditional
+    /// jumps, calls and I/O
+    pub fn extended() -> Self {
+        [SET, ADD, MUL, JZ, JNZ, CALL, RET, IN, OUT]
+            .iter()
+            .fold(Self::default(), |dialect, opcode| {
+                dialect.with_opcode(*opcode)
+            })
+            .with_registers(8)
+    }
+
+    /// Adds an opcode, replacing any with the same name
+    pub fn with_opcode(mut self, opcode: Opcode) -> Self {
+        self.opcodes.insert(opcode.name, opcode);
+        self
+    }
+
+    pub fn with_registers(mut self, registers: usize) -> Self {
+        self.registers = registers.max(1);
+        self
+    }
+
+    pub fn registers(&self) -> usize {
+        self.registers
+    }
+
+    fn operand(&self, kind: Kind, s: &str) -> Result<Operand, String> {
+        let register = match s.strip_prefix('r') {
+            Some(r) => Some(
+                r.parse::<usize>()
+                    .ok()
+                    .filter(|r| *r < self.registers)
+                    .ok_or_else(|| format!("Invalid register {}", s))?,
+            ),
+            None => None,
+        };
+        match (kind, register) {
+            (Kind::Register | Kind::Value, Some(r)) => Ok(Operand::Reg(r)),
+            (Kind::Register, None) => Err(format!("Expected a register, found {}", s)),
+            (Kind::Immediate, Some(_)) => Err(format!("Expected a number, found {}", s)),
+            (_, None) => parse_arg(s).map(Operand::Imm),
+        }
+    }
+
+    pub fn parse_op(&self, s: &str) -> Result<Op, String> {
+        let mut parts = s.split_whitespace();
+        let name = parts.next().ok_or_else(|| String::from("Missing opcode"))?;
+        let opcode = *self
+            .opcodes
+            .get(name)
+            .ok_or_else(|| format!("Unknown opcode {}", name))?;
+        let args: Vec<&str> = parts.collect();
+        if args.len() != opcode.operands.len() {
+            return Err(format!(
+                "{} takes {} operands, found {}",
+                name,
+                opcode.operands.len(),
+                args.len()
+            ));
+        }
+        let operands = opcode
+            .operands
+            .iter()
+            .zip(args)
+            .map(|(kind, arg)| self.operand(*kind, arg))
+            .collect::<Result<_, _>>()?;
+        Ok(Op { opcode, operands })
+    }
+
+    /// Parses one op per line, skipping blank lines and `;` comments
+    pub fn parse_program(&self, source: &str) -> Result<Vec<Op>, String> {
+        source
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| (idx, line.split(';').next().unwrap().trim()))
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(idx, line)| {
+                self.parse_op(line)
+                    .map_err(|e| format!("Line {}: {}", idx + 1, e))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::machine::{Halt, Machine};
+    use super::*;
+
+    #[test]
+    fn test_extended_program() {
+        let dialect = Dialect::extended();
+        // Reads n and writes n! using a subroutine
+        let program = dialect
+            .parse_program(
+                "in r1
+set r0 +1
+call +3
+out r0
+jmp +6
+jz r1 +4 ; factorial: multiply r0 by r1 down to 1
+mul r0 r1
+add r1 -1
+jmp -3
+ret",
+            )
+            .unwrap();
+        assert_eq!(program[5].to_string(), "jz r1 +4");
+
+        let mut machine = Machine::from_ops(program.clone(), dialect.registers())
+            .with_loop_detection(false)
+            .with_input(&[5]);
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.output(), &[120]);
+
+        let mut machine =
+            Machine::from_ops(program, dialect.registers()).with_loop_detection(false);
+        assert_eq!(machine.run(), Halt::Fault(Fault::NoInput));
+
+        assert!(dialect.parse_op("mul +1 +2").is_err());
+        assert!(dialect.parse_op("set r8 +1").is_err());
+        assert!(dialect.parse_op("jmp r1").is_err());
+        assert!(Dialect::default().parse_op("mul r0 +2").is_err());
+    }
+
+    #[test]
+    fn test_custom_opcode() {
+        const DOUBLE: Opcode = Opcode {
+            name: "dbl",
+            operands: &[],
+            exec: |cpu, _| {
+                cpu.registers[0] *= 2;
+                cpu.pc += 1;
+                Ok(())
+            },
+        };
+        let dialect = Dialect::default().with_opcode(DOUBLE);
+        let program = dialect.parse_program("acc +3\ndbl\nacc +1").unwrap();
+        let mut machine = Machine::from_ops(program, dialect.registers());
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.acc(), 7);
+        assert_eq!(
+            Op::from(Instruction::Jump(-2)),
+            dialect.parse_op("jmp -2").unwrap()
+        );
+
+        let program = dialect
+            .parse_program("nop +0\njmp +9223372036854775807")
+            .unwrap();
+        assert_eq!(
+            Machine::from_ops(program, 1).run(),
+            Halt::OutOfBounds(isize::MAX as i128 + 1)
+        );
+    }
+}
@@ -0,0 +1,7 @@
+pub mod analysis;
+pub mod asm;
+pub mod instruction;
+pub mod isa;
+pub mod machine;
+pub mod repair;
+pub mod trace;
@@ -0,0 +1,312 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use super::instruction::Instruction;
+use super::isa::{Cpu, Fault, Op};
+
+/// Why a machine stopped running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Halt {
+    /// The program counter moved to just past the last instruction
+    Terminated,
+    /// The next instruction has already run once
+    LoopDetected,
+    /// A jump went further past the end than the next address
+    OutOfBounds(i128),
+    /// A jump went before the first instruction
+    NegativeAddress(i128),
+    /// The configured step limit was reached
+    StepLimit,
+    /// The next instruction is at a breakpoint
+    Breakpoint,
+    /// The condition given to `run_until` was met
+    Condition,
+    /// The instruction couldn't run
+    Fault(Fault),
+}
+
+/// What a jump past the end of the program means. Jumps before the start
+/// are always an error.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Bounds {
+    /// Only landing just past the last instruction terminates
+    #[default]
+    Strict,
+    /// Landing anywhere past the last instruction terminates
+    PastEnd,
+}
+
+impl FromStr for Bounds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "past-end" => Ok(Self::PastEnd),
+            _ => Err(format!("Unknown bounds {}", s)),
+        }
+    }
+}
+
+impl Bounds {
+    /// The address `pc` points to in a program of `len` instructions, or why
+    /// the program halts there
+    pub fn check(&self, pc: i128, len: usize) -> Result<usize, Halt> {
+        if pc < 0 {
+            return Err(Halt::NegativeAddress(pc));
+        }
+        match pc.cmp(&(len as i128)) {
+            Ordering::Less => Ok(pc as usize),
+            Ordering::Equal => Err(Halt::Terminated),
+            Ordering::Greater => match self {
+                Self::Strict => Err(Halt::OutOfBounds(pc)),
+                Self::PastEnd => Err(Halt::Terminated),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    Address(usize),
+    Opcode(&'static str),
+}
+
+/// Machine state that can be saved and restored later
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    cpu: Cpu,
+    visits: Vec<usize>,
+    steps: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine {
+    program: Vec<Op>,
+    cpu: Cpu,
+    /// Times each instruction has run
+    visits: Vec<usize>,
+    steps: usize,
+    step_limit: Option<usize>,
+    bounds: Bounds,
+    detect_loops: bool,
+    breakpoints: HashSet<Breakpoint>,
+}
+
+impl Machine {
+    /// A machine for the console's own `acc`, `jmp` and `nop` instructions
+    pub fn new(program: Vec<Instruction>) -> Self {
+        Self::from_ops(program.into_iter().map(Op::from).collect(), 1)
+    }
+
+    /// A machine for ops of any dialect, with `registers` registers
+    pub fn from_ops(program: Vec<Op>, registers: usize) -> Self {
+        Machine {
+            visits: vec![0; program.len()],
+            program,
+            cpu: Cpu::new(registers),
+            steps: 0,
+            step_limit: None,
+            bounds: Bounds::default(),
+            detect_loops: true,
+            breakpoints: HashSet::new(),
+        }
+    }
+
+    /// Whether running an instruction a second time halts the machine, which
+    /// programs with conditional jumps usually don't want
+    pub fn with_loop_detection(mut self, detect_loops: bool) -> Self {
+        self.detect_loops = detect_loops;
+        self
+    }
+
+    pub fn with_input(mut self, input: &[isize]) -> Self {
+        self.cpu.input.extend(input);
+        self
+    }
+
+    pub fn with_step_limit(mut self, limit: usize) -> Self {
+        self.step_limit = Some(limit);
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn program(&self) -> &[Op] {
+        &self.program
+    }
+
+    pub fn pc(&self) -> i128 {
+        self.cpu.pc
+    }
+
+    pub fn acc(&self) -> isize {
+        self.cpu.registers[0]
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn output(&self) -> &[isize] {
+        &self.cpu.output
+    }
+
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Times the instruction at `address` has run
+    pub fn visits(&self, address: usize) -> usize {
+        self.visits[address]
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        self.breakpoints.remove(breakpoint)
+    }
+
+    /// The instruction the program counter points at, if it is in the program
+    pub fn current(&self) -> Option<&Op> {
+        usize::try_from(self.cpu.pc)
+            .ok()
+            .and_then(|pc| self.program.get(pc))
+    }
+
+    /// Why the machine can't run the next instruction, if it can't
+    pub fn halted(&self) -> Option<Halt> {
+        match self.bounds.check(self.cpu.pc, self.program.len()) {
+            Ok(address) if self.detect_loops && self.visits[address] > 0 => {
+                Some(Halt::LoopDetected)
+            }
+            Ok(_) => None,
+            Err(halt) => Some(halt),
+        }
+    }
+
+    /// Runs the next instruction, or returns why it can't be run
+    pub fn step(&mut self) -> Result<(), Halt> {
+        if let Some(halt) = self.halted() {
+            return Err(halt);
+        }
+        if self.step_limit.is_some_and(|limit| self.steps >= limit) {
+            return Err(Halt::StepLimit);
+        }
+
+        let op = &self.program[self.cpu.pc as usize];
+        self.visits[self.cpu.pc as usize] += 1;
+        self.steps += 1;
+        (op.opcode.exec)(&mut self.cpu, &op.operands).map_err(Halt::Fault)
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        let opcode = match self.current() {
+            Some(op) => op.opcode.name,
+            None => return false,
+        };
+        self.breakpoints
+            .contains(&Breakpoint::Address(self.cpu.pc as usize))
+            || self.breakpoints.contains(&Breakpoint::Opcode(opcode))
+    }
+
+    /// Steps until `condition` holds, a breakpoint is reached or the machine
+    /// halts. Breakpoints are only checked after the first step, so calling
+    /// this again resumes from a breakpoint.
+    pub fn run_until<F: Fn(&Machine) -> bool>(&mut self, condition: F) -> Halt {
+        loop {
+            if condition(self) {
+                return Halt::Condition;
+            }
+            if let Err(halt) = self.step() {
+                return halt;
+            }
+            if self.at_breakpoint() {
+                return Halt::Breakpoint;
+            }
+        }
+    }
+
+    pub fn run(&mut self) -> Halt {
+        self.run_until(|_| false)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cpu: self.cpu.clone(),
+            visits: self.visits.clone(),
+            steps: self.steps,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.cpu.clone_from(&snapshot.cpu);
+        self.visits.clone_from(&snapshot.visits);
+        self.steps = snapshot.steps;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instruction::{parse_instructions, SAMPLE};
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let mut machine = Machine::new(parse_instructions(SAMPLE).unwrap());
+        assert_eq!(machine.run(), Halt::LoopDetected);
+        assert_eq!(machine.acc(), 5);
+        assert_eq!(machine.pc(), 1);
+        assert_eq!(machine.visits(1), 1);
+        assert_eq!(machine.visits(5), 0);
+
+        let mut program = parse_instructions(SAMPLE).unwrap();
+        program[7] = Instruction::Nop(-4);
+        let mut machine = Machine::new(program.clone());
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.acc(), 8);
+
+        program[8] = Instruction::Jump(5);
+        assert_eq!(Machine::new(program.clone()).run(), Halt::OutOfBounds(13));
+        let mut machine = Machine::new(program.clone()).with_bounds(Bounds::PastEnd);
+        assert_eq!(machine.run(), Halt::Terminated);
+        assert_eq!(machine.pc(), 13);
+        program[8] = Instruction::Jump(-9);
+        assert_eq!(
+            Machine::new(program.clone()).run(),
+            Halt::NegativeAddress(-1)
+        );
+        assert_eq!(
+            Machine::new(program).with_step_limit(3).run(),
+            Halt::StepLimit
+        );
+    }
+
+    #[test]
+    fn test_breakpoints_and_snapshots() {
+        let mut machine = Machine::new(parse_instructions(SAMPLE).unwrap());
+        machine.add_breakpoint(Breakpoint::Opcode("jmp"));
+        assert_eq!(machine.run(), Halt::Breakpoint);
+        assert_eq!(machine.pc(), 2);
+
+        let snapshot = machine.snapshot();
+        assert_eq!(machine.run(), Halt::Breakpoint);
+        assert_eq!((machine.pc(), machine.acc()), (7, 2));
+
+        machine.restore(&snapshot);
+        assert_eq!((machine.pc(), machine.acc()), (2, 1));
+        assert!(machine.remove_breakpoint(&Breakpoint::Opcode("jmp")));
+        machine.add_breakpoint(Breakpoint::Address(6));
+        assert_eq!(machine.run(), Halt::Breakpoint);
+        assert_eq!(machine.acc(), 1);
+        assert_eq!(machine.run_until(|m| m.acc() > 4), Halt::Condition);
+        assert_eq!(machine.acc(), 5);
+    }
+}
@@ -1,128 +1,199 @@
+use std::env;
 use std::fs;
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
-enum Instruction {
-    Acc(isize),
-    Jump(isize),
-    Nop(isize),
-}
-
-impl FromStr for Instruction {
-    type Err = ();
+use day_08::analysis::analyze;
+use day_08::asm::{assemble, disassemble};
+use day_08::instruction::parse_instructions;
+use day_08::isa::Dialect;
+use day_08::machine::{Bounds, Machine};
+use day_08::repair::find_repair;
+use day_08::trace::{trace, Format};
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.split(' ').collect();
-        let instruction_str = parts[0];
-        let val: isize = parts[1].parse().unwrap();
-        match instruction_str {
-            "acc" => Ok(Self::Acc(val)),
-            "jmp" => Ok(Self::Jump(val)),
-            "nop" => Ok(Self::Nop(val)),
-            _ => Err(()),
-        }
+/// Removes a `--flag value` pair from the arguments, returning the value if
+/// the flag is present, or an error if it has no value
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let idx = match args.iter().position(|arg| arg == flag) {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+    args.remove(idx);
+    if idx == args.len() {
+        return Err(format!("Missing a value for {}", flag));
     }
+    Ok(Some(args.remove(idx)))
 }
 
-fn parse_instructions(instructions: &str) -> Vec<Instruction> {
-    instructions
-        .split('\n')
-        .map(Instruction::from_str)
-        .filter_map(Result::ok)
-        .collect()
+/// Reads the `--bounds` and `--format` flags
+fn parse_flags(args: &mut Vec<String>) -> Result<(Bounds, Format), String> {
+    // Jumps past the end are errors unless run with `--bounds past-end`
+    let bounds = match take_flag(args, "--bounds")? {
+        Some(b) => Bounds::from_str(&b)?,
+        None => Bounds::default(),
+    };
+    let format = match take_flag(args, "--format")? {
+        Some(f) => Format::from_str(&f)?,
+        None => Format::Listing,
+    };
+    Ok((bounds, format))
 }
 
-fn execute_program(instructions: Vec<Instruction>) -> (isize, bool) {
-    let mut idx: isize = 0;
-    let mut executed_idx: Vec<isize> = vec![];
-    let mut acc: isize = 0;
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let (bounds, format) = match parse_flags(&mut args) {
+        Ok(flags) => flags,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+
+    // Traces the program as repaired for part 2 with `--repaired`
+    let repaired = match args.iter().position(|arg| arg == "--repaired") {
+        Some(idx) => {
+            args.remove(idx);
+            true
+        }
+        None => false,
+    };
 
-    // Get value either before infinite loop starts or on program completion
-    while !executed_idx.contains(&idx) && idx < instructions.len() as isize {
-        executed_idx.push(idx);
-        match instructions[idx as usize] {
-            Instruction::Acc(v) => {
-                acc += v;
-                idx += 1;
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        // Assembles source with labels and comments into puzzle input
+        ["assemble", path] => {
+            match assemble(&fs::read_to_string(path).unwrap()) {
+                Ok(program) => program.iter().for_each(|i| println!("{}", i)),
+                Err(e) => eprintln!("{}", e),
             }
-            Instruction::Jump(v) => {
-                idx += v;
+            return;
+        }
+        // Writes puzzle input back out with labels for jump targets
+        ["disassemble", rest @ ..] => {
+            let path = rest.first().unwrap_or(&"./day-08/input.txt");
+            match parse_instructions(&fs::read_to_string(path).unwrap()) {
+                Ok(program) => print!("{}", disassemble(&program)),
+                Err(e) => eprintln!("{}", e),
             }
-            Instruction::Nop(_) => {
-                idx += 1;
+            return;
+        }
+        // Reports what can be learned about a program without running it
+        ["analyze", rest @ ..] => {
+            let path = rest.first().unwrap_or(&"./day-08/input.txt");
+            let program = match parse_instructions(&fs::read_to_string(path).unwrap()) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            let analysis = analyze(&program, bounds);
+            println!("Unreachable: {:?}", analysis.unreachable);
+            for cycle in analysis.loops.iter() {
+                println!("Loop: {:?}", cycle);
             }
-        };
-    }
-
-    (acc, !executed_idx.contains(&idx))
-}
-
-fn find_changed_instruction_acc(instructions: Vec<Instruction>) -> isize {
-    instructions
-        .iter()
-        .enumerate()
-        .filter(
-            |(_idx, instruction)| matches!(instruction, Instruction::Jump(_) | Instruction::Nop(_)),
-        )
-        .map(|(idx, _)| {
-            let mut modified_instructions = instructions.clone();
-            let instruction_to_change = &modified_instructions[idx];
-            modified_instructions[idx] = match instruction_to_change {
-                Instruction::Jump(v) => Instruction::Nop(*v),
-                Instruction::Nop(v) => Instruction::Jump(*v),
-                _ => unreachable!(""),
+            println!(
+                "Terminating: {} of {}",
+                analysis.terminating.iter().filter(|t| **t).count(),
+                program.len()
+            );
+            for (address, instruction) in analysis.flips.iter() {
+                println!("Flip: {} to {}", address, instruction);
+            }
+            return;
+        }
+        // Records every instruction run, or how often each one ran
+        [command @ ("trace" | "coverage"), rest @ ..] => {
+            let path = rest.first().unwrap_or(&"./day-08/input.txt");
+            let mut program = match parse_instructions(&fs::read_to_string(path).unwrap()) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
             };
-            execute_program(modified_instructions)
-        })
-        .filter(|(_acc, completed)| *completed)
-        .map(|(acc, _)| acc)
-        .take(1)
-        .next()
-        .unwrap()
-}
+            if repaired {
+                match find_repair(&program, bounds) {
+                    Some(repair) => program[repair.address] = repair.instruction,
+                    None => {
+                        eprintln!("No single swap makes the program terminate");
+                        return;
+                    }
+                }
+            }
+            let mut machine = Machine::new(program).with_bounds(bounds);
+            let trace = trace(&mut machine);
+            if *command == "coverage" {
+                print!("{}", trace.coverage_report(machine.program()));
+            } else {
+                match trace.render(format) {
+                    Ok(out) => print!("{}", out),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            return;
+        }
+        // Runs a program in the extended dialect, reading numbers as input
+        ["run", path, inputs @ ..] => {
+            let dialect = Dialect::extended();
+            let program = match dialect.parse_program(&fs::read_to_string(path).unwrap()) {
+                Ok(program) => program,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            };
+            let inputs: Vec<isize> = match inputs.iter().map(|i| i.parse()).collect() {
+                Ok(inputs) => inputs,
+                Err(e) => {
+                    eprintln!("Invalid input: {}", e);
+                    return;
+                }
+            };
+            let mut machine = Machine::from_ops(program, dialect.registers())
+                .with_bounds(bounds)
+                .with_loop_detection(false)
+                .with_step_limit(1_000_000)
+                .with_input(&inputs);
+            let halt = machine.run();
+            machine.output().iter().for_each(|v| println!("{}", v));
+            println!("{:?} with acc {}", halt, machine.acc());
+            return;
+        }
+        _ => {}
+    }
 
-fn main() {
     let input = fs::read_to_string("./day-08/input.txt").unwrap();
+    let instructions = parse_instructions(&input).unwrap();
+    let mut machine = Machine::new(instructions.clone()).with_bounds(bounds);
+    machine.run();
+    println!("Part 1: {}", machine.acc());
 
-    let instructions = parse_instructions(&input);
-    let (acc, _) = execute_program(instructions.clone());
-    println!("Part 1: {}", acc);
-
-    println!("Part 2: {}", find_changed_instruction_acc(instructions));
+    match find_repair(&instructions, bounds) {
+        Some(repair) => println!("Part 2: {}", repair.acc),
+        None => eprintln!("No single swap makes the program terminate"),
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use day_08::instruction::SAMPLE;
+    use day_08::repair::run_once;
+
     use super::*;
 
     #[test]
     fn test_sample_1() {
-        let sample = "nop +0
-acc +1
-jmp +4
-acc +3
-jmp -3
-acc -99
-acc +1
-jmp -4
-acc +6";
-        let instructions = parse_instructions(sample);
-        let (acc, _) = execute_program(instructions);
-        assert_eq!(acc, 5);
+        let instructions = parse_instructions(SAMPLE).unwrap();
+        assert_eq!(run_once(&instructions, Bounds::Strict).acc, 5);
     }
 
     #[test]
     fn test_sample_2() {
-        let sample = "nop +0
-acc +1
-jmp +4
-acc +3
-jmp -3
-acc -99
-acc +1
-jmp -4
-acc +6";
-        let instructions = parse_instructions(sample);
-        assert_eq!(find_changed_instruction_acc(instructions), 8);
+        let instructions = parse_instructions(SAMPLE).unwrap();
+        assert_eq!(find_repair(&instructions, Bounds::Strict).unwrap().acc, 8);
     }
 }
@@ -0,0 +1,176 @@
+use super::analysis::Cfg;
+use super::instruction::Instruction;
+use super::isa::Fault;
+use super::machine::{Bounds, Halt};
+
+/// One bit per instruction address
+struct Bitset(Vec<u64>);
+
+impl Bitset {
+    fn new(len: usize) -> Self {
+        Bitset(vec![0; len.div_ceil(64)])
+    }
+
+    /// Sets the bit, returning whether it was unset before
+    fn insert(&mut self, idx: usize) -> bool {
+        let (word, bit) = (idx / 64, 1 << (idx % 64));
+        let unset = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        unset
+    }
+}
+
+/// A run of a program that stops before any instruction runs twice
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub acc: isize,
+    /// Either `Halt::LoopDetected`, where the program counter left the
+    /// program, or `Halt::Fault` when the accumulator overflows
+    pub halt: Halt,
+    /// Addresses in the order they ran
+    pub path: Vec<usize>,
+}
+
+pub fn run_once(program: &[Instruction], bounds: Bounds) -> Run {
+    let mut visited = Bitset::new(program.len());
+    let mut acc: isize = 0;
+    let mut path = vec![];
+    let mut pc: i128 = 0;
+
+    let halt = loop {
+        let address = match bounds.check(pc, program.len()) {
+            Ok(address) => address,
+            Err(halt) => break halt,
+        };
+        if !visited.insert(address) {
+            break Halt::LoopDetected;
+        }
+        path.push(address);
+        if let Instruction::Acc(v) = program[address] {
+            acc = match acc.checked_add(v) {
+                Some(acc) => acc,
+                None => break Halt::Fault(Fault::Overflow),
+            };
+        }
+        pc = program[address].next(address);
+    };
+
+    Run { acc, halt, path }
+}
+
+/// A single `jmp`/`nop` swap that makes a program terminate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repair {
+    pub address: usize,
+    /// The instruction that replaces the one at `address`
+    pub instruction: Instruction,
+    /// Accumulator once the repaired program terminates
+    pub acc: isize,
+}
+
+/// Finds the first swap along the program's path that makes it terminate, in
+/// time linear in the length of the program. Gives `None` if the repaired
+/// program's accumulator overflows.
+pub fn find_repair(program: &[Instruction], bounds: Bounds) -> Option<Repair> {
+    let (address, instruction) = Cfg::new(program, bounds)
+        .flips(program, bounds)
+        .into_iter()
+        .next()?;
+
+    let mut repaired = program.to_vec();
+    repaired[address] = instruction;
+    let run = run_once(&repaired, bounds);
+    (run.halt == Halt::Terminated).then_some(Repair {
+        address,
+        instruction,
+        acc: run.acc,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instruction::{parse_instructions, SAMPLE};
+    use super::*;
+
+    #[test]
+    fn test_run_once() {
+        let run = run_once(&parse_instructions(SAMPLE).unwrap(), Bounds::Strict);
+        assert_eq!(run.acc, 5);
+        assert_eq!(run.halt, Halt::LoopDetected);
+        assert_eq!(run.path, vec![0, 1, 2, 6, 7, 3, 4]);
+
+        let program = parse_instructions("acc +9223372036854775807\nacc +1\njmp -2").unwrap();
+        let run = run_once(&program, Bounds::Strict);
+        assert_eq!(run.halt, Halt::Fault(Fault::Overflow));
+        assert_eq!((run.acc, run.path), (isize::MAX, vec![0, 1]));
+        assert_eq!(find_repair(&program, Bounds::Strict), None);
+        assert_eq!(
+            Cfg::new(&parse_instructions(SAMPLE).unwrap(), Bounds::Strict).terminating(),
+            vec![false, false, false, false, false, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_find_repair() {
+        assert_eq!(
+            find_repair(&parse_instructions(SAMPLE).unwrap(), Bounds::Strict),
+            Some(Repair {
+                address: 7,
+                instruction: Instruction::Nop(-4),
+                acc: 8,
+            })
+        );
+        assert_eq!(
+            find_repair(
+                &parse_instructions("jmp +0\nacc +1\njmp -2").unwrap(),
+                Bounds::Strict
+            ),
+            None
+        );
+        // The program already terminates, and swapping the nop jumps back to
+        // the start, so it would run the swap again and loop
+        assert_eq!(
+            find_repair(
+                &parse_instructions("acc +1\nnop -1").unwrap(),
+                Bounds::Strict
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let program = parse_instructions("nop +3\nacc +1\njmp -2\nacc +2").unwrap();
+        assert_eq!(run_once(&program, Bounds::Strict).halt, Halt::LoopDetected);
+        // Swapping the first instruction jumps straight to the last
+        assert_eq!(find_repair(&program, Bounds::Strict).unwrap().acc, 2);
+
+        let program = parse_instructions("acc +1\njmp +5\nacc +2").unwrap();
+        assert_eq!(
+            run_once(&program, Bounds::Strict).halt,
+            Halt::OutOfBounds(6)
+        );
+        assert_eq!(run_once(&program, Bounds::PastEnd).halt, Halt::Terminated);
+        assert_eq!(
+            Cfg::new(&program, Bounds::Strict).terminating(),
+            vec![false, false, true]
+        );
+        let repair = find_repair(&program, Bounds::Strict).unwrap();
+        assert_eq!((repair.address, repair.acc), (1, 3));
+        assert_eq!(
+            run_once(&parse_instructions("jmp -3").unwrap(), Bounds::PastEnd).halt,
+            Halt::NegativeAddress(-3)
+        );
+
+        let program = parse_instructions("nop +0\njmp +9223372036854775807").unwrap();
+        assert_eq!(
+            run_once(&program, Bounds::Strict).halt,
+            Halt::OutOfBounds(isize::MAX as i128 + 1)
+        );
+        assert_eq!(
+            Cfg::new(&program, Bounds::Strict).terminating(),
+            vec![false, false]
+        );
+        assert_eq!(find_repair(&program, Bounds::Strict).unwrap().address, 1);
+    }
+}
@@ -0,0 +1,195 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use super::isa::Op;
+use super::machine::{Halt, Machine};
+
+/// One instruction the machine ran
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Step {
+    pub address: usize,
+    pub opcode: &'static str,
+    /// Operands as they're written in source, separated by spaces
+    pub argument: String,
+    pub acc_before: isize,
+    pub acc_after: isize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+    pub halt: Halt,
+    /// Times each instruction ran
+    pub coverage: Vec<usize>,
+}
+
+/// Runs the machine until it halts, recording every instruction. Breakpoints
+/// are ignored.
+pub fn trace(machine: &mut Machine) -> Trace {
+    let mut steps = vec![];
+    let halt = loop {
+        let (address, op, acc_before) = match machine.current() {
+            Some(op) => (machine.pc() as usize, op.clone(), machine.acc()),
+            None => break machine.halted().unwrap_or(Halt::Terminated),
+        };
+        let result = machine.step();
+        // A fault still counts as running the instruction, which leaves the
+        // accumulator as it was
+        let acc_after = match result {
+            Ok(()) => machine.acc(),
+            Err(Halt::Fault(_)) => acc_before,
+            Err(halt) => break halt,
+        };
+        steps.push(Step {
+            address,
+            opcode: op.opcode.name,
+            argument: op
+                .operands
+                .iter()
+                .map(|operand| operand.to_string())
+                .collect::<Vec<String>>()
+                .join(" "),
+            acc_before,
+            acc_after,
+        });
+        if let Err(halt) = result {
+            break halt;
+        }
+    };
+
+    Trace {
+        steps,
+        halt,
+        coverage: (0..machine.program().len())
+            .map(|address| machine.visits(address))
+            .collect(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One JSON object per step
+    Json,
+    /// One line per step with the accumulator before and after
+    Listing,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "listing" => Ok(Self::Listing),
+            _ => Err(format!("Unknown format {}", s)),
+        }
+    }
+}
+
+impl Trace {
+    pub fn render(&self, format: Format) -> Result<String, serde_json::Error> {
+        let mut out = String::new();
+        for step in self.steps.iter() {
+            out += &match format {
+                Format::Json => serde_json::to_string(step)?,
+                Format::Listing => format!(
+                    "{:>5}  {:<12} acc {} -> {}",
+                    step.address,
+                    format!("{} {}", step.opcode, step.argument),
+                    step.acc_before,
+                    step.acc_after
+                ),
+            };
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    /// Each instruction of the program with the times it ran, followed by how
+    /// many ran at all
+    pub fn coverage_report(&self, program: &[Op]) -> String {
+        let mut out = String::new();
+        for (address, (op, count)) in program.iter().zip(self.coverage.iter()).enumerate() {
+            out += &format!("{:>6}  {:>5}  {}\n", count, address, op);
+        }
+        out += &format!(
+            "Ran {} of {} instructions, halted with {:?}\n",
+            self.coverage.iter().filter(|count| **count > 0).count(),
+            program.len(),
+            self.halt
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::instruction::{parse_instructions, SAMPLE};
+    use super::super::isa::{Dialect, Fault};
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let mut machine = Machine::new(parse_instructions(SAMPLE).unwrap());
+        let trace = trace(&mut machine);
+        assert_eq!(trace.halt, Halt::LoopDetected);
+        assert_eq!(
+            trace
+                .steps
+                .iter()
+                .map(|s| s.address)
+                .collect::<Vec<usize>>(),
+            vec![0, 1, 2, 6, 7, 3, 4]
+        );
+        assert_eq!(trace.coverage, vec![1, 1, 1, 1, 1, 0, 1, 1, 0]);
+        assert_eq!(
+            trace.steps[5],
+            Step {
+                address: 3,
+                opcode: "acc",
+                argument: String::from("+3"),
+                acc_before: 2,
+                acc_after: 5,
+            }
+        );
+        assert_eq!(
+            trace.render(Format::Json).unwrap().lines().nth(1),
+            Some(r#"{"address":1,"opcode":"acc","argument":"+1","acc_before":0,"acc_after":1}"#)
+        );
+    }
+
+    #[test]
+    fn test_listing_and_coverage() {
+        let mut machine = Machine::new(parse_instructions("acc +2\nnop -1").unwrap());
+        let trace = trace(&mut machine);
+        assert_eq!(
+            trace.render(Format::Listing).unwrap(),
+            "    0  acc +2       acc 0 -> 2\n    1  nop -1       acc 2 -> 2\n"
+        );
+        assert_eq!(
+            trace.coverage_report(machine.program()),
+            "     1      0  acc +2\n     1      1  nop -1\nRan 2 of 2 instructions, halted with Terminated\n"
+        );
+    }
+
+    #[test]
+    fn test_faults() {
+        // Faulting instructions are traced as well as counted
+        let mut machine =
+            Machine::new(parse_instructions("acc +9223372036854775807\nacc +1").unwrap());
+        let overflow = trace(&mut machine);
+        assert_eq!(overflow.halt, Halt::Fault(Fault::Overflow));
+        assert_eq!(overflow.steps.len(), 2);
+        assert_eq!(overflow.coverage, vec![1, 1]);
+        assert_eq!(overflow.steps[1].acc_after, overflow.steps[1].acc_before);
+
+        let dialect = Dialect::extended();
+        let program = dialect.parse_program("in r1\nout r1").unwrap();
+        let mut machine = Machine::from_ops(program, dialect.registers());
+        let no_input = trace(&mut machine);
+        assert_eq!(no_input.halt, Halt::Fault(Fault::NoInput));
+        assert_eq!(no_input.steps.len(), 1);
+        assert_eq!(no_input.coverage, vec![1, 0]);
+    }
+}
@@ -0,0 +1,76 @@
+use std::str::Lines;
+
+/// Iterator over records in input where records are separated by blank lines
+pub struct Records<'a> {
+    lines: Lines<'a>,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = vec![];
+        for line in self.lines.by_ref() {
+            // Lines with only whitespace separate records like empty ones
+            let line = line.trim_end();
+            if !line.is_empty() {
+                record.push(line);
+            } else if !record.is_empty() {
+                break;
+            }
+        }
+        if record.is_empty() {
+            None
+        } else {
+            Some(record)
+        }
+    }
+}
+
+/// Splits input into records of non-blank lines. Handles CRLF line endings
+/// and trailing whitespace, and never yields an empty record no matter how
+/// many blank lines separate, lead or trail the records.
+pub fn records(input: &str) -> Records<'_> {
+    Records {
+        lines: input.lines(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_records() {
+        assert_eq!(
+            records("a\nb\n\nc").collect::<Vec<_>>(),
+            vec![vec!["a", "b"], vec!["c"]]
+        );
+    }
+
+    #[test]
+    fn test_crlf() {
+        assert_eq!(
+            records("a\r\nb\r\n\r\nc\r\n").collect::<Vec<_>>(),
+            vec![vec!["a", "b"], vec!["c"]]
+        );
+    }
+
+    #[test]
+    fn test_trailing_and_leading_blank_lines() {
+        assert_eq!(
+            records("\n\na\n\n\n\nb\n\n\n").collect::<Vec<_>>(),
+            vec![vec!["a"], vec!["b"]]
+        );
+        assert_eq!(records("").count(), 0);
+        assert_eq!(records("\n\n").count(), 0);
+    }
+
+    #[test]
+    fn test_whitespace_separators() {
+        assert_eq!(
+            records("a  \n \t \nb\n   \n").collect::<Vec<_>>(),
+            vec![vec!["a"], vec!["b"]]
+        );
+    }
+}